@@ -1,5 +1,5 @@
 pub const USER_STACK_SIZE: usize = 4096 * 2;
-pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
+pub const KERNEL_STACK_SIZE: usize = 4096 * 4;
 pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
 pub const CLOCK_FREQ: usize = 10_000_000;
 
@@ -17,3 +17,15 @@ pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 
 pub const BIG_STRIDE: u8 = u8::MAX;
+
+/// Harts the kernel brings up, entry.asm has a boot stack for each.
+pub const MAX_HARTS: usize = 4;
+
+/// CPU affinity of a task allowed to run on every hart.
+pub const ALL_HARTS_MASK: usize = (1 << MAX_HARTS) - 1;
+
+/// A hart that went this long without scheduling is reported as locked up.
+pub const LOCKUP_TIMEOUT_US: usize = 10_000_000;
+
+/// A user task blocked for this long is reported as hung.
+pub const HUNG_TASK_TIMEOUT_US: usize = 120_000_000;
@@ -2,6 +2,7 @@ use core::fmt::Write;
 use core::{fmt, usize};
 
 use crate::sbi::console_putchar;
+use crate::sync::SpinLock;
 
 struct Stdout;
 
@@ -15,8 +16,11 @@ impl fmt::Write for Stdout {
     }
 }
 
+/// Serializes output of all harts so their lines do not interleave.
+static STDOUT: SpinLock<Stdout> = SpinLock::new(Stdout);
+
 pub fn print(args: fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    STDOUT.lock().write_fmt(args).unwrap();
 }
 
 #[macro_export]
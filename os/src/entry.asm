@@ -1,15 +1,20 @@
 .section .text.entry
 .globl _start
 
+# a0 = hart id, every hart gets its own 64 KiB boot stack below boot_stack_top
 _start:
+  mv tp, a0
+  slli t0, a0, 16
   la sp, boot_stack_top
+  sub sp, sp, t0
   call rust_main
 
   .section .bss.stack
   .global boot_stack_lower_bound
 
 boot_stack_lower_bound:
-  .space 4096 * 16
+  # 4096 * 16 bytes for each of the MAX_HARTS harts
+  .space 4096 * 16 * 4
   .global boot_stack_top
 
 boot_stack_top:
@@ -0,0 +1,53 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{File, Stdin, Stdout};
+use crate::sync::SpinLock;
+
+/// Open files of a task, file descriptors are indices into the list.
+#[derive(Clone, Default)]
+pub struct FdTable {
+    files: Vec<Option<Arc<dyn File>>>,
+}
+
+impl FdTable {
+    /// A table with stdin and stdout open at 0 and 1, for the initproc.
+    pub fn new() -> Arc<SpinLock<Self>> {
+        Arc::new(SpinLock::new(Self {
+            files: vec![Some(Arc::new(Stdin)), Some(Arc::new(Stdout))],
+        }))
+    }
+
+    /// A copy of the table, for a child that does not share it.
+    pub fn fork(&self) -> Arc<SpinLock<Self>> {
+        Arc::new(SpinLock::new(self.clone()))
+    }
+
+    pub fn get(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.files.get(fd)?.clone()
+    }
+
+    /// Opens `file` at the lowest free descriptor below `limit`, returns it.
+    pub fn alloc(&mut self, file: Arc<dyn File>, limit: usize) -> Option<usize> {
+        let fd = self
+            .files
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.files.len());
+        if fd >= limit {
+            return None;
+        }
+        if fd == self.files.len() {
+            self.files.push(Some(file));
+        } else {
+            self.files[fd] = Some(file);
+        }
+        Some(fd)
+    }
+
+    /// Closes `fd`, returns the file it referred to unless it was not open.
+    pub fn close(&mut self, fd: usize) -> Option<Arc<dyn File>> {
+        self.files.get_mut(fd)?.take()
+    }
+}
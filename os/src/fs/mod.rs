@@ -0,0 +1,27 @@
+mod fd_table;
+mod pipe;
+mod stdio;
+
+use alloc::vec::Vec;
+
+pub use fd_table::FdTable;
+pub use pipe::make_pipe;
+#[cfg(feature = "selftest")]
+pub use pipe::pipe_test;
+pub use stdio::{Stdin, Stdout};
+
+/// A user buffer, split where it crosses pages, as `translated_byte_buffer` returns it.
+pub type UserBuffer = Vec<&'static mut [u8]>;
+
+/// Something a file descriptor refers to. Reads and writes return how many bytes they moved, or
+/// a negative error like syscalls.
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    fn read(&self, buf: UserBuffer) -> isize;
+    fn write(&self, buf: UserBuffer) -> isize;
+    /// Whether this is the console, which terminal control applies to.
+    fn is_tty(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,265 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{File, UserBuffer};
+use crate::sync::{Sleeper, SpinLock, WaitQueue, WakeReason, EINTR};
+use crate::task::{current_task, send_signal, SIGPIPE};
+
+/// Bytes a pipe holds at most, writers block once it is full.
+const PIPE_BUFFER_SIZE: usize = 4096;
+
+/// Returned by writes to a pipe whose read end is closed, Linux's `-EPIPE`.
+const EPIPE: isize = -32;
+
+/// Ring buffer shared by both ends of a pipe.
+struct PipeBuffer {
+    data: Vec<u8>,
+    /// Where the oldest byte is.
+    head: usize,
+    len: usize,
+    read_end_open: bool,
+    write_end_open: bool,
+    /// Tasks waiting for data or for the write end to close.
+    readers: WaitQueue,
+    /// Tasks waiting for room or for the read end to close.
+    writers: WaitQueue,
+}
+
+impl PipeBuffer {
+    /// Moves as many bytes as there are, up to the length of `out`, into `out`.
+    fn pop(&mut self, out: &mut [u8]) -> usize {
+        let n = out.len().min(self.len);
+        for byte in out.iter_mut().take(n) {
+            *byte = self.data[self.head];
+            self.head = (self.head + 1) % PIPE_BUFFER_SIZE;
+        }
+        self.len -= n;
+        n
+    }
+
+    /// Appends as much of `input` as there is room for.
+    fn push(&mut self, input: &[u8]) -> usize {
+        let n = input.len().min(PIPE_BUFFER_SIZE - self.len);
+        for &byte in &input[..n] {
+            self.data[(self.head + self.len) % PIPE_BUFFER_SIZE] = byte;
+            self.len += 1;
+        }
+        n
+    }
+}
+
+/// The read or the write end of a pipe, closed once the last descriptor referring to it is.
+pub struct Pipe {
+    writable: bool,
+    buffer: Arc<SpinLock<PipeBuffer>>,
+}
+
+/// Creates a pipe, returns its read end and its write end.
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    let buffer = Arc::new(SpinLock::new(PipeBuffer {
+        data: vec![0; PIPE_BUFFER_SIZE],
+        head: 0,
+        len: 0,
+        read_end_open: true,
+        write_end_open: true,
+        readers: WaitQueue::default(),
+        writers: WaitQueue::default(),
+    }));
+    let read_end = Arc::new(Pipe {
+        writable: false,
+        buffer: buffer.clone(),
+    });
+    let write_end = Arc::new(Pipe {
+        writable: true,
+        buffer,
+    });
+    (read_end, write_end)
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        !self.writable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Reads what the pipe holds, blocking until it holds anything. Returns 0 at end of file.
+    fn read(&self, buf: UserBuffer) -> isize {
+        if !self.readable() {
+            return -1;
+        }
+        if buf.is_empty() {
+            return 0;
+        }
+        let mut buffer = self.buffer.lock();
+        while buffer.len == 0 {
+            if !buffer.write_end_open {
+                return 0;
+            }
+            let Ok(sleeper) = Sleeper::prepare(true) else {
+                return EINTR;
+            };
+            buffer.readers.push(sleeper.clone());
+            drop(buffer);
+            let reason = sleeper.sleep();
+            buffer = self.buffer.lock();
+            if reason == WakeReason::Interrupted {
+                buffer.readers.remove(&sleeper);
+                return EINTR;
+            }
+        }
+        let mut read = 0;
+        for piece in buf {
+            let n = buffer.pop(piece);
+            read += n;
+            if n < piece.len() {
+                break;
+            }
+        }
+        buffer.writers.wake_all();
+        read as isize
+    }
+
+    /// Writes all of `buf`, blocking whenever the pipe is full. Stops early on a signal or once
+    /// the read end closes.
+    fn write(&self, buf: UserBuffer) -> isize {
+        if !self.writable {
+            return -1;
+        }
+        let partial = |written: usize, error: isize| {
+            if written > 0 {
+                written as isize
+            } else {
+                error
+            }
+        };
+        let mut written = 0;
+        for piece in buf {
+            let mut piece: &[u8] = piece;
+            while !piece.is_empty() {
+                let mut buffer = self.buffer.lock();
+                if !buffer.read_end_open {
+                    drop(buffer);
+                    send_signal(&current_task().unwrap(), SIGPIPE);
+                    return partial(written, EPIPE);
+                }
+                let n = buffer.push(piece);
+                if n > 0 {
+                    buffer.readers.wake_all();
+                    piece = &piece[n..];
+                    written += n;
+                    continue;
+                }
+                let Ok(sleeper) = Sleeper::prepare(true) else {
+                    return partial(written, EINTR);
+                };
+                buffer.writers.push(sleeper.clone());
+                drop(buffer);
+                if sleeper.sleep() == WakeReason::Interrupted {
+                    self.buffer.lock().writers.remove(&sleeper);
+                    return partial(written, EINTR);
+                }
+            }
+        }
+        written as isize
+    }
+}
+
+impl Drop for Pipe {
+    /// Closes the end, waking up the tasks on the other end.
+    fn drop(&mut self) {
+        let mut buffer = self.buffer.lock();
+        if self.writable {
+            buffer.write_end_open = false;
+            buffer.readers.wake_all();
+        } else {
+            buffer.read_end_open = false;
+            buffer.writers.wake_all();
+        }
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn pipe_test() {
+    use core::sync::atomic::{AtomicIsize, Ordering};
+
+    use log::info;
+
+    use super::FdTable;
+    use crate::selftest::wait_until;
+    use crate::task::{spawn_kernel_thread, SIGINT};
+
+    let user_buffer = |len| -> UserBuffer { vec![vec![0; len].leak()] };
+
+    // The ring buffer keeps the order of the bytes across its end.
+    let (read_end, write_end) = make_pipe();
+    {
+        let mut buffer = read_end.buffer.lock();
+        let input: Vec<u8> = (0..PIPE_BUFFER_SIZE).map(|i| i as u8).collect();
+        let mut out = vec![0; PIPE_BUFFER_SIZE];
+        assert_eq!(buffer.push(&input[..3000]), 3000);
+        assert_eq!(buffer.pop(&mut out[..2000]), 2000);
+        assert_eq!(buffer.push(&input[3000..]), PIPE_BUFFER_SIZE - 3000);
+        assert_eq!(buffer.push(&input[..PIPE_BUFFER_SIZE]), 2000);
+        assert_eq!(buffer.pop(&mut out), PIPE_BUFFER_SIZE);
+        assert!(out[..2096].iter().eq(input[2000..].iter()));
+        assert!(out[2096..].iter().eq(input[..2000].iter()));
+    }
+    assert_eq!(read_end.write(user_buffer(1)), -1);
+    assert_eq!(write_end.read(user_buffer(1)), -1);
+    assert_eq!(read_end.read(Vec::new()), 0);
+
+    // A writer blocks on a full pipe until a reader makes room.
+    assert_eq!(
+        write_end.write(user_buffer(PIPE_BUFFER_SIZE)),
+        PIPE_BUFFER_SIZE as isize
+    );
+    let written = Arc::new(AtomicIsize::new(0));
+    let (thread_write_end, thread_written) = (write_end.clone(), written.clone());
+    spawn_kernel_thread(move || {
+        thread_written.store(thread_write_end.write(user_buffer(10)), Ordering::SeqCst);
+    });
+    wait_until(|| read_end.buffer.lock().writers.iter().count() == 1);
+    assert_eq!(read_end.read(user_buffer(10)), 10);
+    wait_until(|| written.load(Ordering::SeqCst) == 10);
+
+    // What is left is read after the write end closed, then end of file.
+    drop(write_end);
+    assert_eq!(
+        read_end.read(user_buffer(2 * PIPE_BUFFER_SIZE)),
+        PIPE_BUFFER_SIZE as isize
+    );
+    assert_eq!(read_end.read(user_buffer(1)), 0);
+
+    // A signal interrupts a blocked reader, writes to a closed read end fail.
+    let (read_end, write_end) = make_pipe();
+    let result = Arc::new(AtomicIsize::new(0));
+    let (thread_read_end, thread_result) = (read_end.clone(), result.clone());
+    let reader = spawn_kernel_thread(move || {
+        thread_result.store(thread_read_end.read(user_buffer(1)), Ordering::SeqCst);
+    });
+    wait_until(|| read_end.buffer.lock().readers.iter().count() == 1);
+    send_signal(&reader, SIGINT);
+    wait_until(|| result.load(Ordering::SeqCst) == EINTR);
+    drop(read_end);
+    let (thread_write_end, thread_result) = (write_end.clone(), result.clone());
+    spawn_kernel_thread(move || {
+        thread_result.store(thread_write_end.write(user_buffer(1)), Ordering::SeqCst);
+    });
+    wait_until(|| result.load(Ordering::SeqCst) == EPIPE);
+
+    // Descriptors are the lowest free ones, up to the limit.
+    let mut table = FdTable::default();
+    assert_eq!(table.alloc(write_end.clone(), 2), Some(0));
+    assert_eq!(table.alloc(write_end.clone(), 2), Some(1));
+    assert_eq!(table.alloc(write_end.clone(), 2), None);
+    assert!(table.close(0).is_some());
+    assert!(table.close(0).is_none());
+    assert!(table.close(5).is_none());
+    assert_eq!(table.alloc(write_end, 2), Some(0));
+
+    info!("pipe_test passed !");
+}
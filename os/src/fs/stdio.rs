@@ -0,0 +1,82 @@
+use core::str;
+
+use super::{File, UserBuffer};
+use crate::print;
+use crate::sync::{preempt_disable, preempt_enable, EINTR};
+use crate::task::{block_current_and_run_next, current_has_pending_signal, current_task};
+use crate::timer::{add_timer, get_time_us, TICK_US};
+use crate::tty::tty_getchar;
+
+/// Input typed at the console.
+pub struct Stdin;
+
+/// Output to the console.
+pub struct Stdout;
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    /// Reads a single character, blocking until one is typed.
+    fn read(&self, mut buf: UserBuffer) -> isize {
+        let Some(byte) = buf.first_mut().and_then(|piece| piece.first_mut()) else {
+            return 0;
+        };
+        let ch = loop {
+            if let Some(c) = tty_getchar() {
+                break c;
+            }
+            // Interrupted, the signal takes effect on the way back to user mode.
+            if current_has_pending_signal() {
+                return EINTR;
+            }
+            // The console is polled, sleep for a tick instead of spinning.
+            preempt_disable();
+            add_timer(get_time_us() + TICK_US, current_task().unwrap());
+            block_current_and_run_next();
+            preempt_enable();
+        };
+        *byte = ch;
+        1
+    }
+
+    fn write(&self, _buf: UserBuffer) -> isize {
+        -1
+    }
+
+    fn is_tty(&self) -> bool {
+        true
+    }
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: UserBuffer) -> isize {
+        -1
+    }
+
+    fn write(&self, buf: UserBuffer) -> isize {
+        let mut len = 0;
+        for piece in buf {
+            print!("{}", str::from_utf8(piece).unwrap());
+            len += piece.len();
+        }
+        len as isize
+    }
+
+    fn is_tty(&self) -> bool {
+        true
+    }
+}
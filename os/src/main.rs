@@ -14,16 +14,21 @@ extern crate alloc;
 
 mod config;
 mod console;
+mod fs;
 pub mod loader;
 mod logging;
 mod mm;
 mod sbi;
+#[cfg(feature = "selftest")]
+mod selftest;
+mod smp;
 mod stack_trace;
 mod sync;
 pub mod syscall;
 mod task;
 mod timer;
 pub mod trap;
+mod tty;
 
 global_asm!(include_str!("entry.asm"));
 global_asm!(include_str!("link_app.S"));
@@ -49,21 +54,44 @@ fn panic(info: &PanicInfo) -> ! {
 }
 
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize) -> ! {
+    if smp::is_booted() {
+        secondary_main(hart_id);
+    }
+
     clear_bss();
     logging::init();
     info!("[kernel] Hello, world!");
     mm::init();
     task::add_initproc();
+    task::init_workqueue();
     info!("[kernel] after initproc!");
     trap::init();
     trap::enable_timer_interrupt();
+    trap::enable_ipi();
     timer::set_next_trigger();
     loader::list_apps();
+    #[cfg(feature = "selftest")]
+    selftest::start();
+    smp::boot_other_harts(hart_id);
+    smp::set_online();
     task::run_tasks();
     unreachable!("rust_main");
 }
 
+/// Entry of the harts started by the boot hart.
+fn secondary_main(hart_id: usize) -> ! {
+    mm::init_hart();
+    info!("[kernel] Hart {} is up", hart_id);
+    trap::init();
+    trap::enable_timer_interrupt();
+    trap::enable_ipi();
+    timer::set_next_trigger();
+    smp::set_online();
+    task::run_tasks();
+    unreachable!("secondary_main");
+}
+
 fn clear_bss() {
     extern "C" {
         fn sbss();
@@ -6,7 +6,7 @@ use lazy_static::lazy_static;
 use super::address::PhysPageNum;
 use crate::config::MEMORY_END;
 use crate::mm::address::PhysAddr;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 
 trait FrameAllocator {
     fn new() -> Self;
@@ -22,8 +22,8 @@ pub struct StackFrameAllocator {
 }
 
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<StackFrameAllocator> =
-        unsafe { UPSafeCell::new(StackFrameAllocator::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinLock<StackFrameAllocator> =
+        SpinLock::new(StackFrameAllocator::new());
 }
 
 impl StackFrameAllocator {
@@ -65,21 +65,18 @@ pub fn init_frame_allocator() {
         fn ekernel();
     }
 
-    FRAME_ALLOCATOR.exclusive_access().init(
+    FRAME_ALLOCATOR.lock().init(
         PhysAddr::from(ekernel as usize).ceil(),
         PhysAddr::from(MEMORY_END).floor(),
     );
 }
 
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
-        .exclusive_access()
-        .alloc()
-        .map(FrameTracker::new)
+    FRAME_ALLOCATOR.lock().alloc().map(FrameTracker::new)
 }
 
 pub fn frame_dealloc(ppn: PhysPageNum) {
-    FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
+    FRAME_ALLOCATOR.lock().dealloc(ppn);
 }
 
 pub struct FrameTracker {
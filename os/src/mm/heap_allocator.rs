@@ -1,18 +1,35 @@
-use core::alloc::Layout;
+use core::alloc::{GlobalAlloc, Layout};
 
 use buddy_system_allocator::LockedHeap;
 use log::info;
 
 use crate::config::KERNEL_HEAP_SIZE;
+use crate::sync::IrqGuard;
+
+/// The heap with interrupts disabled while it is locked, interrupt handlers allocate as well.
+struct KernelHeap(LockedHeap<32>);
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _irq = IrqGuard::new();
+        self.0.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _irq = IrqGuard::new();
+        self.0.dealloc(ptr, layout)
+    }
+}
 
 #[global_allocator]
-static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::empty();
+static HEAP_ALLOCATOR: KernelHeap = KernelHeap(LockedHeap::empty());
 
 static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
 pub fn init_heap() {
     unsafe {
         HEAP_ALLOCATOR
+            .0
             .lock()
             .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
     }
@@ -2,6 +2,8 @@ use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec;
 use core::arch::asm;
+use core::mem::size_of;
+use core::sync::atomic::AtomicU32;
 
 use bitflags::bitflags;
 use lazy_static::lazy_static;
@@ -12,7 +14,7 @@ use super::address::{PhysAddr, PhysPageNum, VPNRange, VirtAddr, VirtPageNum};
 use super::frame_allocator::{frame_alloc, FrameTracker};
 use super::page_table::{PTEFlags, PageTable, PageTableEntry};
 use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum MapType {
@@ -60,6 +62,22 @@ impl MapArea {
         }
     }
 
+    pub fn resident_pages(&self) -> usize {
+        self.data_frames.len()
+    }
+
+    pub fn start_va(&self) -> VirtAddr {
+        self.vpn_range.get_start().into()
+    }
+
+    pub fn end_va(&self) -> VirtAddr {
+        self.vpn_range.get_end().into()
+    }
+
+    pub fn permission(&self) -> MapPermission {
+        self.map_perm
+    }
+
     pub fn map(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
             self.map_once(page_table, vpn);
@@ -163,8 +181,8 @@ extern "C" {
 }
 
 lazy_static! {
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinLock<MemorySet>> =
+        Arc::new(SpinLock::new(MemorySet::new_kernel()));
 }
 
 pub struct MemorySet {
@@ -239,14 +257,86 @@ impl MemorySet {
         self.page_table.translate(vpn)
     }
 
-    pub fn recycle_data_pages(&mut self) {
-        self.areas.clear();
+    /// The aligned word at `va` if its page is mapped for user mode, whatever its permissions.
+    pub fn user_word(&self, va: VirtAddr) -> Option<&'static mut usize> {
+        if !va.page_offset().is_multiple_of(size_of::<usize>()) {
+            return None;
+        }
+        let pte = self.translate(va.floor())?;
+        if !pte.is_valid() || !pte.flags().contains(PTEFlags::U) {
+            return None;
+        }
+        let pa: PhysAddr = pte.ppn().into();
+        Some(PhysAddr::from(usize::from(pa) + va.page_offset()).get_mut())
+    }
+
+    /// Contents of the page at `va` if it is mapped for user mode.
+    pub fn user_page(&self, va: VirtAddr) -> Option<&'static [u8]> {
+        let pte = self.translate(va.floor())?;
+        if !pte.flags().contains(PTEFlags::V | PTEFlags::U) {
+            return None;
+        }
+        Some(pte.ppn().get_bytes_array())
+    }
+
+    /// The aligned 32-bit word at `va` if its page is mapped readable for user mode, along with
+    /// its physical address.
+    pub fn user_u32(&self, va: VirtAddr) -> Option<(usize, &'static AtomicU32)> {
+        if !va.page_offset().is_multiple_of(size_of::<u32>()) {
+            return None;
+        }
+        let pte = self.translate(va.floor())?;
+        if !pte
+            .flags()
+            .contains(PTEFlags::V | PTEFlags::U | PTEFlags::R)
+        {
+            return None;
+        }
+        let pa = usize::from(PhysAddr::from(pte.ppn())) + va.page_offset();
+        Some((pa, PhysAddr::from(pa).get_ref()))
+    }
+
+    /// The byte at `va` if its page is mapped for user mode with at least the permissions `perm`.
+    pub fn user_byte(&self, va: VirtAddr, perm: MapPermission) -> Option<&'static mut u8> {
+        let pte = self.translate(va.floor())?;
+        let required = PTEFlags::V | PTEFlags::U | PTEFlags::from_bits(perm.bits()).unwrap();
+        if !pte.flags().contains(required) {
+            return None;
+        }
+        let pa: PhysAddr = pte.ppn().into();
+        Some(PhysAddr::from(usize::from(pa) + va.page_offset()).get_mut())
+    }
+
+    /// Detaches all areas, their frames are freed whenever the caller drops them. The page table
+    /// still maps those frames and must not be activated again.
+    pub fn take_areas(&mut self) -> vec::Vec<MapArea> {
+        core::mem::take(&mut self.areas)
     }
 
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
 
+    /// Areas user mode can access.
+    pub fn user_areas(&self) -> impl Iterator<Item = &MapArea> {
+        self.areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+    }
+
+    /// Number of pages covered by the areas of the address space, mapped or not.
+    pub fn mapped_pages(&self) -> usize {
+        self.areas
+            .iter()
+            .map(|area| area.vpn_range.get_end().0 - area.vpn_range.get_start().0)
+            .sum()
+    }
+
+    /// Number of frames backing the areas of the address space.
+    pub fn resident_pages(&self) -> usize {
+        self.areas.iter().map(|area| area.resident_pages()).sum()
+    }
+
     pub fn new_kernel() -> Self {
         let mut memory_set = Self::new_bare();
         memory_set.map_trampoline();
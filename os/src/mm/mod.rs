@@ -5,11 +5,16 @@ mod memory_set;
 mod page_table;
 
 pub use address::{PhysPageNum, VirtAddr};
-pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE};
+pub use memory_set::{MapArea, MapPermission, MemorySet, KERNEL_SPACE};
 pub use page_table::{translated_byte_buffer, translated_mut, translated_ref, translated_str};
 
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
-    KERNEL_SPACE.exclusive_access().activate();
+    KERNEL_SPACE.lock().activate();
+}
+
+/// Switches a secondary hart to the kernel address space built by the boot hart.
+pub fn init_hart() {
+    KERNEL_SPACE.lock().activate();
 }
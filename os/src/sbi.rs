@@ -1,6 +1,6 @@
 #![allow(unused)]
 
-use sbi_rt::{legacy, system_reset, NoReason, Shutdown, SystemFailure};
+use sbi_rt::{hart_start, legacy, send_ipi, system_reset, NoReason, Shutdown, SystemFailure};
 
 pub fn console_putchar(c: usize) {
     #[allow(deprecated)]
@@ -12,6 +12,16 @@ pub fn console_getchar() -> usize {
     legacy::console_getchar()
 }
 
+/// Starts `hart_id` at `start_addr` with `opaque` in a1, returns whether it did.
+pub fn start_hart(hart_id: usize, start_addr: usize, opaque: usize) -> bool {
+    hart_start(hart_id, start_addr, opaque).error == 0
+}
+
+/// Raises a supervisor software interrupt on every hart set in `hart_mask`.
+pub fn send_ipi_mask(hart_mask: usize) {
+    send_ipi(hart_mask, 0);
+}
+
 pub fn shutdown(failure: bool) -> ! {
     if !failure {
         system_reset(Shutdown, NoReason);
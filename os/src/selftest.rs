@@ -0,0 +1,65 @@
+use log::info;
+
+use crate::fs::pipe_test;
+use crate::sbi::shutdown;
+use crate::smp::smp_test;
+use crate::sync::{deadlock_test, futex_test, pi_test, preempt_test, wait_queue_test};
+use crate::task::{
+    affinity_test, cfs_test, clone_test, coredump_test, cred_test, edf_test, mlfq_test,
+    ptrace_test, rlimit_test, seccomp_test, signal_test, spawn_kernel_thread,
+    suspend_current_and_run_next, usage_test, watchdog_test, workqueue_test,
+};
+use crate::timer::{get_time_us, timer_test, TICK_US};
+use crate::trap::{crash_test, fp_test, misaligned_test};
+
+/// Runs the self-tests in a kernel thread, some of them block, and powers off once all passed. A
+/// failing one panics.
+pub fn start() {
+    spawn_kernel_thread(|| {
+        cfs_test();
+        mlfq_test();
+        edf_test();
+        smp_test();
+        timer_test();
+        affinity_test();
+        usage_test();
+        rlimit_test();
+        workqueue_test();
+        preempt_test();
+        clone_test();
+        signal_test();
+        cred_test();
+        seccomp_test();
+        ptrace_test();
+        coredump_test();
+        crash_test();
+        misaligned_test();
+        fp_test();
+        watchdog_test();
+        wait_queue_test();
+        deadlock_test();
+        pi_test();
+        futex_test();
+        pipe_test();
+        info!("[kernel] All self-tests passed.");
+        shutdown(false);
+    });
+}
+
+/// Yields the hart until `cond` holds, fails if that takes a hundred ticks.
+pub fn wait_until(cond: impl Fn() -> bool) {
+    wait(cond, suspend_current_and_run_next);
+}
+
+/// Spins until `cond` holds without yielding, so only preemption lets others get on.
+pub fn spin_until(cond: impl Fn() -> bool) {
+    wait(cond, core::hint::spin_loop);
+}
+
+fn wait(cond: impl Fn() -> bool, relax: fn()) {
+    let deadline = get_time_us() + 100 * TICK_US;
+    while !cond() {
+        assert!(get_time_us() < deadline, "self-test timed out");
+        relax();
+    }
+}
@@ -0,0 +1,120 @@
+use core::arch::asm;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use log::warn;
+
+use crate::config::MAX_HARTS;
+use crate::sbi::{send_ipi_mask, start_hart};
+
+/// Set by the boot hart once the kernel is initialized.
+static BOOTED: AtomicBool = AtomicBool::new(false);
+
+/// Harts that joined the scheduler, one bit per hart.
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Harts whose timer is not ticking, one bit per hart.
+static TICKLESS_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Id of the running hart, kept in `tp`.
+pub fn hart_id() -> usize {
+    let id;
+    unsafe {
+        asm!("mv {}, tp", out(reg) id);
+    }
+    id
+}
+
+pub fn is_booted() -> bool {
+    BOOTED.load(Ordering::Acquire)
+}
+
+/// Starts all other harts at `_start`, they join the scheduler through `rust_main`.
+pub fn boot_other_harts(boot_hart: usize) {
+    extern "C" {
+        fn _start();
+    }
+
+    BOOTED.store(true, Ordering::Release);
+    for hart in (0..MAX_HARTS).filter(|&hart| hart != boot_hart) {
+        if !start_hart(hart, _start as usize, 0) {
+            warn!("[kernel] Failed to start hart {}", hart);
+        }
+    }
+}
+
+pub fn set_tickless(tickless: bool) {
+    let bit = 1 << hart_id();
+    if tickless {
+        TICKLESS_HARTS.fetch_or(bit, Ordering::SeqCst);
+    } else {
+        TICKLESS_HARTS.fetch_and(!bit, Ordering::SeqCst);
+    }
+}
+
+/// Sends an IPI to every tickless hart, so they notice a newly ready task.
+pub fn kick_tickless_harts() {
+    let mask = TICKLESS_HARTS.swap(0, Ordering::SeqCst);
+    if mask != 0 {
+        send_ipi_mask(mask);
+    }
+}
+
+/// Supervisor software interrupt pending bit of `sip`.
+const SIP_SSIP: usize = 1 << 1;
+
+/// Acknowledges a pending IPI of this hart.
+pub fn clear_ipi() {
+    unsafe {
+        asm!("csrc sip, {}", in(reg) SIP_SSIP);
+    }
+}
+
+/// Marks the running hart as able to run tasks.
+pub fn set_online() {
+    ONLINE_HARTS.fetch_or(1 << hart_id(), Ordering::SeqCst);
+}
+
+pub fn online_harts() -> usize {
+    ONLINE_HARTS.load(Ordering::SeqCst)
+}
+
+#[cfg(feature = "selftest")]
+pub fn smp_test() {
+    use alloc::sync::Arc;
+
+    use log::info;
+    use riscv::register::sstatus;
+
+    use crate::selftest::wait_until;
+    use crate::sync::SpinLock;
+    use crate::task::{spawn_kernel_thread, suspend_current_and_run_next};
+
+    const THREADS: usize = 8;
+    const ROUNDS: usize = 1000;
+
+    let counter = Arc::new(SpinLock::new(0));
+    let harts = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+    for _ in 0..THREADS {
+        let (counter, harts, done) = (counter.clone(), harts.clone(), done.clone());
+        spawn_kernel_thread(move || {
+            for round in 0..ROUNDS {
+                let mut counter = counter.lock();
+                assert!(!sstatus::read().sie());
+                *counter += 1;
+                drop(counter);
+                harts.fetch_or(1 << hart_id(), Ordering::SeqCst);
+                if round % 100 == 0 {
+                    suspend_current_and_run_next();
+                }
+            }
+            done.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    wait_until(|| done.load(Ordering::SeqCst) == THREADS);
+    assert_eq!(*counter.lock(), THREADS * ROUNDS);
+    let harts = harts.load(Ordering::SeqCst);
+    assert_eq!(harts & !online_harts(), 0);
+
+    info!("smp_test passed on harts {:#x} !", harts);
+}
@@ -0,0 +1,37 @@
+use super::{Interrupted, Mutex, Sleeper, SpinLock, WaitQueue, WakeReason};
+
+/// A condition variable of user programs.
+pub struct Condvar {
+    wait_queue: SpinLock<WaitQueue>,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            wait_queue: SpinLock::new(WaitQueue::default()),
+        }
+    }
+
+    /// Wakes up the task that waited longest, if any.
+    pub fn signal(&self) {
+        self.wait_queue.lock().wake_one();
+    }
+
+    /// Releases `mutex`, calls `on_released` and waits for a signal; the caller takes the mutex
+    /// again. Returns `Ok(false)` if the caller did not hold the mutex.
+    pub fn wait(&self, mutex: &dyn Mutex, on_released: impl FnOnce()) -> Result<bool, Interrupted> {
+        let mut wait_queue = self.wait_queue.lock();
+        if !mutex.unlock() {
+            return Ok(false);
+        }
+        on_released();
+        let sleeper = Sleeper::prepare(true)?;
+        wait_queue.push(sleeper.clone());
+        drop(wait_queue);
+        if sleeper.sleep() == WakeReason::Woken {
+            return Ok(true);
+        }
+        self.wait_queue.lock().remove(&sleeper);
+        Err(Interrupted)
+    }
+}
@@ -0,0 +1,171 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// A mutex or semaphore of a `SyncTable`, by its id.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Resource {
+    Mutex(usize),
+    Semaphore(usize),
+}
+
+/// Units of each resource, absent ones are 0.
+type Vector = BTreeMap<Resource, usize>;
+
+fn inc(vector: &mut Vector, resource: Resource) {
+    *vector.entry(resource).or_default() += 1;
+}
+
+fn dec(vector: &mut Vector, resource: Resource) -> bool {
+    match vector.get_mut(&resource) {
+        Some(units) if *units > 1 => *units -= 1,
+        Some(_) => {
+            vector.remove(&resource);
+        }
+        None => return false,
+    }
+    true
+}
+
+/// The Available, Allocation and Need matrices of the banker's algorithm for the resources of a
+/// `SyncTable`, Need only counting the units a task waits for right now.
+#[derive(Default)]
+pub struct DeadlockDetector {
+    pub enabled: bool,
+    /// Units left of each resource, briefly negative while a release is not accounted for.
+    available: BTreeMap<Resource, isize>,
+    allocation: BTreeMap<usize, Vector>,
+    need: BTreeMap<usize, Vector>,
+}
+
+impl DeadlockDetector {
+    pub fn add_resource(&mut self, resource: Resource, units: usize) {
+        self.available.insert(resource, units as isize);
+    }
+
+    /// Records that task `tid` is about to take a unit of `resource`, returns false if that
+    /// would deadlock with detection enabled.
+    pub fn request(&mut self, tid: usize, resource: Resource) -> bool {
+        inc(self.need.entry(tid).or_default(), resource);
+        let would_block = self
+            .available
+            .get(&resource)
+            .is_some_and(|&units| units <= 0);
+        if self.enabled && would_block && !self.is_safe() {
+            self.cancel(tid, resource);
+            return false;
+        }
+        true
+    }
+
+    /// Records that task `tid` got the unit of `resource` it requested.
+    pub fn acquire(&mut self, tid: usize, resource: Resource) {
+        self.cancel(tid, resource);
+        inc(self.allocation.entry(tid).or_default(), resource);
+        *self.available.entry(resource).or_default() -= 1;
+    }
+
+    /// Records that task `tid` gave back a unit of `resource`.
+    pub fn release(&mut self, tid: usize, resource: Resource) {
+        if let Some(allocation) = self.allocation.get_mut(&tid) {
+            dec(allocation, resource);
+            if allocation.is_empty() {
+                self.allocation.remove(&tid);
+            }
+        }
+        *self.available.entry(resource).or_default() += 1;
+    }
+
+    /// Forgets task `tid`, which exited. The semaphore units it took stay taken.
+    pub fn forget(&mut self, tid: usize) {
+        self.need.remove(&tid);
+        for (resource, units) in self.allocation.remove(&tid).into_iter().flatten() {
+            if let Resource::Mutex(_) = resource {
+                *self.available.entry(resource).or_default() += units as isize;
+            }
+        }
+    }
+
+    /// Records that task `tid` no longer waits for the unit of `resource` it requested.
+    pub fn cancel(&mut self, tid: usize, resource: Resource) {
+        if let Some(need) = self.need.get_mut(&tid) {
+            dec(need, resource);
+            if need.is_empty() {
+                self.need.remove(&tid);
+            }
+        }
+    }
+
+    /// The safety algorithm: whether the tasks can finish one after the other.
+    fn is_safe(&self) -> bool {
+        let mut work = self.available.clone();
+        let mut unfinished: Vec<usize> = self
+            .allocation
+            .keys()
+            .chain(self.need.keys())
+            .copied()
+            .collect();
+        unfinished.sort_unstable();
+        unfinished.dedup();
+
+        let satisfiable = |tid: &usize, work: &BTreeMap<Resource, isize>| {
+            self.need.get(tid).is_none_or(|need| {
+                need.iter().all(|(resource, &units)| {
+                    work.get(resource).copied().unwrap_or(0) >= units as isize
+                })
+            })
+        };
+        while let Some(idx) = unfinished.iter().position(|tid| satisfiable(tid, &work)) {
+            let tid = unfinished.swap_remove(idx);
+            for (&resource, &units) in self.allocation.get(&tid).into_iter().flatten() {
+                *work.entry(resource).or_default() += units as isize;
+            }
+        }
+        unfinished.is_empty()
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn deadlock_test() {
+    use log::info;
+
+    let (m0, m1, sem) = (
+        Resource::Mutex(0),
+        Resource::Mutex(1),
+        Resource::Semaphore(0),
+    );
+    let mut detector = DeadlockDetector::default();
+    detector.add_resource(m0, 1);
+    detector.add_resource(m1, 1);
+    detector.add_resource(sem, 1);
+    let take = |detector: &mut DeadlockDetector, tid, resource| {
+        assert!(detector.request(tid, resource));
+        detector.acquire(tid, resource);
+    };
+
+    // Two tasks taking two mutexes in opposite order, the second wait closes the cycle.
+    take(&mut detector, 1, m0);
+    take(&mut detector, 2, m1);
+    assert!(detector.request(1, m1));
+    assert!(detector.request(2, m0));
+    detector.cancel(2, m0);
+    detector.enabled = true;
+    assert!(!detector.request(2, m0));
+    assert!(!detector.need.contains_key(&2));
+
+    // Once the second task exits, its mutex goes to the first.
+    detector.forget(2);
+    detector.acquire(1, m1);
+    detector.release(1, m0);
+    detector.release(1, m1);
+    assert!(detector.allocation.is_empty() && detector.need.is_empty());
+    assert!(detector.available.values().all(|&units| units == 1));
+
+    // A semaphore unit taken by an exited task stays taken, waiting for it is a deadlock.
+    take(&mut detector, 3, sem);
+    detector.forget(3);
+    assert!(!detector.request(4, sem));
+    detector.release(5, sem);
+    take(&mut detector, 4, sem);
+
+    info!("deadlock_test passed !");
+}
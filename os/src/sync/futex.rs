@@ -0,0 +1,162 @@
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use lazy_static::lazy_static;
+
+use super::{Sleeper, SpinLock, WakeReason};
+use crate::timer::{add_timeout, get_time_us};
+
+/// Why `futex_wait` returned without being woken up.
+pub enum FutexError {
+    /// The futex did not hold the expected value.
+    WouldBlock,
+    TimedOut,
+    Interrupted,
+}
+
+/// A task blocked in `futex_wait`.
+struct FutexWaiter {
+    /// Futex the task waits on, changed by `futex_requeue` under the lock of `FUTEXES`.
+    key: AtomicUsize,
+    sleeper: Arc<Sleeper>,
+}
+
+type FutexQueues = BTreeMap<usize, VecDeque<Arc<FutexWaiter>>>;
+
+lazy_static! {
+    /// Tasks waiting on each futex, keyed by the physical address of the futex word.
+    static ref FUTEXES: SpinLock<FutexQueues> = SpinLock::new(BTreeMap::new());
+}
+
+/// Takes up to `n` waiters off the futex `key`, from the one that waited longest.
+fn take_waiters(futexes: &mut FutexQueues, key: usize, n: usize) -> VecDeque<Arc<FutexWaiter>> {
+    let Some(queue) = futexes.get_mut(&key) else {
+        return VecDeque::new();
+    };
+    let taken = queue.drain(..n.min(queue.len())).collect();
+    if queue.is_empty() {
+        futexes.remove(&key);
+    }
+    taken
+}
+
+/// Wakes up waiters off the futex `key` until `n` of them woke up, returns how many did.
+fn wake_waiters(futexes: &mut FutexQueues, key: usize, n: usize) -> usize {
+    let mut woken = 0;
+    while woken < n {
+        let Some(waiter) = take_waiters(futexes, key, 1).pop_front() else {
+            break;
+        };
+        // Not counted if its timeout or a signal woke it up already.
+        if waiter.sleeper.wake(WakeReason::Woken) {
+            woken += 1;
+        }
+    }
+    woken
+}
+
+/// Blocks the running task on the futex `key` if `word`, the futex word, still holds `expected`.
+/// The value is checked under the lock wakers take.
+pub fn futex_wait(
+    key: usize,
+    word: &AtomicU32,
+    expected: u32,
+    timeout_us: Option<usize>,
+) -> Result<(), FutexError> {
+    let mut futexes = FUTEXES.lock();
+    if word.load(Ordering::SeqCst) != expected {
+        return Err(FutexError::WouldBlock);
+    }
+    let sleeper = Sleeper::prepare(true).map_err(|_| FutexError::Interrupted)?;
+    let waiter = Arc::new(FutexWaiter {
+        key: AtomicUsize::new(key),
+        sleeper: sleeper.clone(),
+    });
+    futexes.entry(key).or_default().push_back(waiter.clone());
+    drop(futexes);
+    if let Some(timeout_us) = timeout_us {
+        add_timeout(get_time_us() + timeout_us, &sleeper);
+    }
+    let reason = sleeper.sleep();
+    if reason == WakeReason::Woken {
+        return Ok(());
+    }
+    // Still queued unless a wakeup took it off too late.
+    let mut futexes = FUTEXES.lock();
+    let key = waiter.key.load(Ordering::Relaxed);
+    if let Some(queue) = futexes.get_mut(&key) {
+        queue.retain(|other| !Arc::ptr_eq(other, &waiter));
+        if queue.is_empty() {
+            futexes.remove(&key);
+        }
+    }
+    if reason == WakeReason::TimedOut {
+        Err(FutexError::TimedOut)
+    } else {
+        Err(FutexError::Interrupted)
+    }
+}
+
+/// Wakes up to `n` tasks waiting on the futex `key`, returns how many it woke up.
+pub fn futex_wake(key: usize, n: usize) -> usize {
+    wake_waiters(&mut FUTEXES.lock(), key, n)
+}
+
+/// Wakes up to `n_wake` tasks waiting on the futex `key` and moves up to `n_requeue` of the
+/// others over to `key2`. Returns how many tasks it woke up.
+pub fn futex_requeue(key: usize, n_wake: usize, key2: usize, n_requeue: usize) -> usize {
+    let mut futexes = FUTEXES.lock();
+    let woken = wake_waiters(&mut futexes, key, n_wake);
+    let moved = take_waiters(&mut futexes, key, n_requeue);
+    if !moved.is_empty() {
+        moved
+            .iter()
+            .for_each(|waiter| waiter.key.store(key2, Ordering::Relaxed));
+        futexes.entry(key2).or_default().extend(moved);
+    }
+    woken
+}
+
+#[cfg(feature = "selftest")]
+pub fn futex_test() {
+    use log::info;
+
+    use crate::selftest::wait_until;
+    use crate::task::spawn_kernel_thread;
+    use crate::timer::TICK_US;
+
+    // Keys no futex word has, physical memory starts higher up.
+    let (key, key2) = (1, 2);
+    let waiters = |key| FUTEXES.lock().get(&key).map_or(0, VecDeque::len);
+    let word = Arc::new(AtomicU32::new(0));
+    assert!(matches!(
+        futex_wait(key, &word, 1, None),
+        Err(FutexError::WouldBlock)
+    ));
+    assert!(matches!(
+        futex_wait(key, &word, 0, Some(2 * TICK_US)),
+        Err(FutexError::TimedOut)
+    ));
+    assert_eq!(waiters(key), 0);
+
+    let woken = Arc::new(AtomicUsize::new(0));
+    for _ in 0..3 {
+        let (word, woken) = (word.clone(), woken.clone());
+        spawn_kernel_thread(move || {
+            assert!(futex_wait(key, &word, 0, None).is_ok());
+            woken.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    wait_until(|| waiters(key) == 3);
+
+    // One woken, one moved over to the other futex, one left.
+    assert_eq!(futex_requeue(key, 1, key2, 1), 1);
+    assert_eq!((waiters(key), waiters(key2)), (1, 1));
+    assert_eq!(futex_wake(key2, 5), 1);
+    assert_eq!(futex_wake(key, 5), 1);
+    assert_eq!(futex_wake(key, 5), 0);
+    wait_until(|| woken.load(Ordering::SeqCst) == 3);
+
+    info!("futex_test passed !");
+}
@@ -1,3 +1,37 @@
+mod condvar;
+mod deadlock;
+mod futex;
+mod mutex;
+mod preempt;
+mod semaphore;
+mod spin;
+mod table;
 mod up;
+mod wait_queue;
 
+pub use condvar::Condvar;
+#[cfg(feature = "selftest")]
+pub use deadlock::deadlock_test;
+pub use deadlock::{DeadlockDetector, Resource};
+#[cfg(feature = "selftest")]
+pub use futex::futex_test;
+pub use futex::{futex_requeue, futex_wait, futex_wake, FutexError};
+#[cfg(feature = "selftest")]
+pub use mutex::pi_test;
+pub use mutex::{
+    priority_changed, release_held_mutexes, InversionStats, Mutex, MutexBlocking, MutexSpin,
+    MutexState,
+};
+#[cfg(feature = "selftest")]
+pub use preempt::preempt_test;
+pub use preempt::{
+    disable_interrupts, enable_interrupts, preempt_count, preempt_disable, preempt_enable,
+    restore_interrupts, set_need_resched, switch_in, switch_out, IrqGuard,
+};
+pub use semaphore::Semaphore;
+pub use spin::{SpinLock, SpinLockGuard};
+pub use table::SyncTable;
 pub use up::UPSafeCell;
+#[cfg(feature = "selftest")]
+pub use wait_queue::wait_queue_test;
+pub use wait_queue::{Interrupted, Sleeper, WaitQueue, WakeReason, EINTR};
@@ -0,0 +1,331 @@
+use alloc::sync::{Arc, Weak};
+
+use super::{Interrupted, Sleeper, SpinLock, WaitQueue, WakeReason};
+use crate::task::{
+    current_has_pending_signal, current_task, suspend_current_and_run_next, TaskControlBlock,
+};
+use crate::timer::get_time_us;
+
+/// Longest chain of tasks waiting for each other's mutexes a priority is passed along.
+const MAX_PI_CHAIN: usize = 16;
+
+/// Serializes all changes to who holds and waits for blocking mutexes.
+static PI_LOCK: SpinLock<()> = SpinLock::new(());
+
+/// A mutex of user programs.
+pub trait Mutex: Sync + Send {
+    /// Returns once the running task holds the mutex, or a signal interrupted the wait.
+    fn lock(&self) -> Result<(), Interrupted>;
+    /// Releases the mutex, returns false if the running task does not hold it.
+    fn unlock(&self) -> bool;
+    /// Priority inversions seen on the mutex, `None` if it does not track them.
+    fn inversion_stats(&self) -> Option<InversionStats> {
+        None
+    }
+}
+
+/// How often and how long tasks waited for a mutex held by a task of lower priority.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct InversionStats {
+    /// Times a task blocked on the mutex while its holder had a lower priority.
+    pub inversions: usize,
+    /// Times the holder of the mutex got a higher priority lent by a waiter.
+    pub boosts: usize,
+    /// Total and longest time in microseconds such tasks waited.
+    pub inverted_wait_us: usize,
+    pub max_inverted_wait_us: usize,
+}
+
+/// Yields the hart until the mutex is free, for locks held briefly.
+pub struct MutexSpin {
+    /// Pid of the holder.
+    owner: SpinLock<Option<usize>>,
+}
+
+impl MutexSpin {
+    pub fn new() -> Self {
+        Self {
+            owner: SpinLock::new(None),
+        }
+    }
+}
+
+impl Mutex for MutexSpin {
+    fn lock(&self) -> Result<(), Interrupted> {
+        let pid = current_task().unwrap().getpid();
+        loop {
+            let mut owner = self.owner.lock();
+            if owner.is_none() {
+                *owner = Some(pid);
+                return Ok(());
+            }
+            drop(owner);
+            if current_has_pending_signal() {
+                return Err(Interrupted);
+            }
+            suspend_current_and_run_next();
+        }
+    }
+
+    fn unlock(&self) -> bool {
+        let pid = current_task().unwrap().getpid();
+        let mut owner = self.owner.lock();
+        if *owner != Some(pid) {
+            return false;
+        }
+        *owner = None;
+        true
+    }
+}
+
+/// Blocks waiting tasks until the mutex is handed to them in turn, waiters lend their priority to
+/// the holder.
+pub struct MutexBlocking {
+    state: Arc<SpinLock<MutexState>>,
+}
+
+/// Who holds and waits for a blocking mutex, referred to by those tasks.
+pub struct MutexState {
+    owner: Option<Weak<TaskControlBlock>>,
+    wait_queue: WaitQueue,
+    stats: InversionStats,
+}
+
+impl MutexBlocking {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(SpinLock::new(MutexState {
+                owner: None,
+                wait_queue: WaitQueue::default(),
+                stats: InversionStats::default(),
+            })),
+        }
+    }
+}
+
+/// Sets the priority `task` inherits from the waiters of its mutexes, returns whether it went up
+/// or `None` if it did not change. `PI_LOCK` is held and no mutex state.
+fn update_inherited_priority(task: &Arc<TaskControlBlock>) -> Option<bool> {
+    let held = task.inner_exclusive_access().pi_held.clone();
+    let inherited = held
+        .iter()
+        .filter_map(|mutex| {
+            let state = mutex.lock();
+            state
+                .wait_queue
+                .iter()
+                .map(|waiter| waiter.inner_exclusive_access().sched.priority)
+                .max()
+        })
+        .max()
+        .unwrap_or(0);
+    let mut inner = task.inner_exclusive_access();
+    let old = inner.sched.priority;
+    inner
+        .sched
+        .set_inherited_priority(inherited)
+        .then(|| inner.sched.priority > old)
+}
+
+/// Passes a change of the priority of `task` on along the chain of mutex holders. `PI_LOCK` is
+/// held.
+fn propagate_priority(task: &Arc<TaskControlBlock>) {
+    let mut task = task.clone();
+    for _ in 0..MAX_PI_CHAIN {
+        let Some(mutex) = task.inner_exclusive_access().pi_blocked_on.clone() else {
+            return;
+        };
+        let Some(owner) = mutex.lock().owner.as_ref().and_then(Weak::upgrade) else {
+            return;
+        };
+        match update_inherited_priority(&owner) {
+            None => return,
+            Some(raised) => {
+                if raised {
+                    mutex.lock().stats.boosts += 1;
+                }
+            }
+        }
+        task = owner;
+    }
+}
+
+/// Passes `mutex` held by `owner` on to the task that waited longest for it. `PI_LOCK` is held.
+fn hand_over(mutex: &Arc<SpinLock<MutexState>>, owner: &Arc<TaskControlBlock>) {
+    let mut state = mutex.lock();
+    // A waiter gets the mutex still locked, nobody can take it in between.
+    let next = state.wait_queue.wake_one();
+    state.owner = next.as_ref().map(Arc::downgrade);
+    drop(state);
+
+    owner
+        .inner_exclusive_access()
+        .pi_held
+        .retain(|held| !Arc::ptr_eq(held, mutex));
+    if let Some(next) = next {
+        let mut next_inner = next.inner_exclusive_access();
+        next_inner.pi_blocked_on = None;
+        next_inner.pi_held.push(mutex.clone());
+        drop(next_inner);
+        // The remaining waiters lend their priority to the new holder.
+        update_inherited_priority(&next);
+    }
+    // Back to what the mutexes the old holder still holds lend it.
+    if update_inherited_priority(owner).is_some() {
+        propagate_priority(owner);
+    }
+}
+
+/// Hands the blocking mutexes held by `task`, which exits, on to their waiters.
+pub fn release_held_mutexes(task: &Arc<TaskControlBlock>) {
+    let _pi = PI_LOCK.lock();
+    let held = task.inner_exclusive_access().pi_held.clone();
+    for mutex in held {
+        hand_over(&mutex, task);
+    }
+    task.inner_exclusive_access().pi_blocked_on = None;
+}
+
+/// Passes a new priority of `task` on to the holders of the mutexes it waits for.
+pub fn priority_changed(task: &Arc<TaskControlBlock>) {
+    let _pi = PI_LOCK.lock();
+    propagate_priority(task);
+}
+
+impl MutexBlocking {
+    /// Takes `task`, which a signal woke up, off the waiters.
+    fn cancel_wait(&self, task: &Arc<TaskControlBlock>, sleeper: &Arc<Sleeper>) {
+        let _pi = PI_LOCK.lock();
+        let mut state = self.state.lock();
+        state.wait_queue.remove(sleeper);
+        let owner = state.owner.as_ref().and_then(Weak::upgrade);
+        drop(state);
+        task.inner_exclusive_access().pi_blocked_on = None;
+        if let Some(owner) = owner {
+            if update_inherited_priority(&owner).is_some() {
+                propagate_priority(&owner);
+            }
+        }
+    }
+}
+
+impl Mutex for MutexBlocking {
+    fn lock(&self) -> Result<(), Interrupted> {
+        let pi = PI_LOCK.lock();
+        let task = current_task().unwrap();
+        let mut state = self.state.lock();
+        let Some(owner) = state.owner.as_ref().and_then(Weak::upgrade) else {
+            state.owner = Some(Arc::downgrade(&task));
+            drop(state);
+            task.inner_exclusive_access()
+                .pi_held
+                .push(self.state.clone());
+            return Ok(());
+        };
+        let sleeper = Sleeper::prepare(true)?;
+        let owner_priority = owner.inner_exclusive_access().sched.priority;
+        let inverted = owner_priority < task.inner_exclusive_access().sched.priority;
+        if inverted {
+            state.stats.inversions += 1;
+        }
+        state.wait_queue.push(sleeper.clone());
+        drop(state);
+        task.inner_exclusive_access().pi_blocked_on = Some(self.state.clone());
+        propagate_priority(&task);
+        drop(pi);
+
+        let start = get_time_us();
+        if sleeper.sleep() == WakeReason::Interrupted {
+            self.cancel_wait(&task, &sleeper);
+            return Err(Interrupted);
+        }
+        // Handed the mutex by `unlock`, which did the bookkeeping.
+        if inverted {
+            let waited = get_time_us() - start;
+            let mut state = self.state.lock();
+            state.stats.inverted_wait_us += waited;
+            state.stats.max_inverted_wait_us = state.stats.max_inverted_wait_us.max(waited);
+        }
+        Ok(())
+    }
+
+    fn unlock(&self) -> bool {
+        let _pi = PI_LOCK.lock();
+        let task = current_task().unwrap();
+        let held = self
+            .state
+            .lock()
+            .owner
+            .as_ref()
+            .is_some_and(|owner| Weak::as_ptr(owner) == Arc::as_ptr(&task));
+        if !held {
+            return false;
+        }
+        hand_over(&self.state, &task);
+        true
+    }
+
+    fn inversion_stats(&self) -> Option<InversionStats> {
+        Some(self.state.lock().stats)
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn pi_test() {
+    use alloc::boxed::Box;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use log::info;
+
+    use crate::selftest::wait_until;
+    use crate::task::add_task;
+
+    let priority = |task: &Arc<TaskControlBlock>| task.inner_exclusive_access().sched.priority;
+    let me = current_task().unwrap();
+    let base = priority(&me);
+    let step = Arc::new(AtomicUsize::new(0));
+
+    // A waiter of higher priority lends it to the holder until the mutex is handed over.
+    let mutex = Arc::new(MutexBlocking::new());
+    assert!(mutex.lock().is_ok());
+    let (thread_mutex, thread_step) = (mutex.clone(), step.clone());
+    let waiter = Arc::new(TaskControlBlock::new_kernel_thread(Box::new(move || {
+        assert!(thread_mutex.lock().is_ok());
+        thread_step.store(1, Ordering::SeqCst);
+        assert!(thread_mutex.unlock());
+    })));
+    waiter
+        .inner_exclusive_access()
+        .set_priority(base as isize + 10);
+    add_task(waiter.clone());
+    wait_until(|| priority(&me) == base + 10);
+    let stats = mutex.inversion_stats().unwrap();
+    assert_eq!((stats.inversions, stats.boosts), (1, 1));
+
+    waiter
+        .inner_exclusive_access()
+        .set_priority(base as isize + 5);
+    priority_changed(&waiter);
+    assert_eq!(priority(&me), base + 5);
+    assert!(mutex.unlock());
+    assert_eq!(priority(&me), base);
+    wait_until(|| step.load(Ordering::SeqCst) == 1);
+
+    // The mutexes of an exiting holder go to their waiters.
+    assert!(mutex.lock().is_ok());
+    let (thread_mutex, thread_step) = (mutex.clone(), step.clone());
+    let waiter = Arc::new(TaskControlBlock::new_kernel_thread(Box::new(move || {
+        assert!(thread_mutex.lock().is_ok());
+        thread_step.store(2, Ordering::SeqCst);
+        assert!(thread_mutex.unlock());
+    })));
+    add_task(waiter.clone());
+    wait_until(|| waiter.inner_exclusive_access().pi_blocked_on.is_some());
+    release_held_mutexes(&me);
+    assert!(me.inner_exclusive_access().pi_held.is_empty());
+    wait_until(|| step.load(Ordering::SeqCst) == 2);
+    assert!(!mutex.unlock());
+
+    info!("pi_test passed !");
+}
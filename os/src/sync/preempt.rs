@@ -0,0 +1,185 @@
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use riscv::register::sstatus;
+
+use crate::config::MAX_HARTS;
+use crate::smp::hart_id;
+use crate::task::preempt_current_and_run_next;
+
+/// Interrupt and preemption state of one hart.
+struct HartState {
+    /// Nesting depth of `IrqGuard`s.
+    irq_depth: AtomicUsize,
+    /// Whether interrupts were enabled before the outermost `IrqGuard`.
+    irq_enabled: AtomicBool,
+    /// Nesting depth of `preempt_disable`, saved and restored with the running task.
+    preempt_count: AtomicUsize,
+    /// A timer interrupt wanted to preempt the task while preemption was disabled.
+    need_resched: AtomicBool,
+}
+
+impl HartState {
+    const fn new() -> Self {
+        Self {
+            irq_depth: AtomicUsize::new(0),
+            irq_enabled: AtomicBool::new(false),
+            preempt_count: AtomicUsize::new(0),
+            need_resched: AtomicBool::new(false),
+        }
+    }
+}
+
+static HARTS: [HartState; MAX_HARTS] = [const { HartState::new() }; MAX_HARTS];
+
+/// State of the running hart, only valid while interrupts are disabled.
+fn local() -> &'static HartState {
+    &HARTS[hart_id()]
+}
+
+/// Disables interrupts on this hart, returns whether they were enabled.
+pub fn disable_interrupts() -> bool {
+    let enabled = sstatus::read().sie();
+    unsafe {
+        sstatus::clear_sie();
+    }
+    enabled
+}
+
+pub fn enable_interrupts() {
+    unsafe {
+        sstatus::set_sie();
+    }
+}
+
+/// Enables interrupts again if `enabled`, the value returned by `disable_interrupts`.
+pub fn restore_interrupts(enabled: bool) {
+    if enabled {
+        enable_interrupts();
+    }
+}
+
+/// Keeps interrupts disabled on this hart while alive. Nests, the outermost guard restores them.
+pub struct IrqGuard {
+    _private: (),
+}
+
+impl IrqGuard {
+    pub fn new() -> Self {
+        let enabled = disable_interrupts();
+        let hart = local();
+        if hart.irq_depth.fetch_add(1, Ordering::Relaxed) == 0 {
+            hart.irq_enabled.store(enabled, Ordering::Relaxed);
+        }
+        Self { _private: () }
+    }
+}
+
+impl Drop for IrqGuard {
+    fn drop(&mut self) {
+        assert!(!sstatus::read().sie(), "interrupts enabled inside IrqGuard");
+        let hart = local();
+        if hart.irq_depth.fetch_sub(1, Ordering::Relaxed) == 1
+            && hart.irq_enabled.load(Ordering::Relaxed)
+        {
+            enable_interrupts();
+        }
+    }
+}
+
+/// Keeps the running task from being preempted until the matching `preempt_enable`.
+pub fn preempt_disable() {
+    let _irq = IrqGuard::new();
+    local().preempt_count.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Ends a `preempt_disable`, preempting the task if a timer interrupt asked for it meanwhile.
+pub fn preempt_enable() {
+    // With interrupts disabled the caller may still hold a spin lock.
+    let enabled = disable_interrupts();
+    let hart = local();
+    let count = hart.preempt_count.fetch_sub(1, Ordering::Relaxed) - 1;
+    let resched = count == 0 && enabled && hart.need_resched.swap(false, Ordering::Relaxed);
+    restore_interrupts(enabled);
+
+    if resched {
+        preempt_current_and_run_next();
+    }
+}
+
+/// Nesting depth of `preempt_disable` of the interrupted task, for interrupt handlers.
+pub fn preempt_count() -> usize {
+    let _irq = IrqGuard::new();
+    local().preempt_count.load(Ordering::Relaxed)
+}
+
+/// Defers a preemption to the next `preempt_enable` that makes the task preemptible.
+pub fn set_need_resched() {
+    let _irq = IrqGuard::new();
+    local().need_resched.store(true, Ordering::Relaxed);
+}
+
+/// Preemption state of the task switching away, for `switch_in`. Interrupts must be disabled.
+pub fn switch_out() -> usize {
+    let hart = local();
+    // Switching away is what a pending preemption asked for.
+    hart.need_resched.store(false, Ordering::Relaxed);
+    hart.preempt_count.swap(0, Ordering::Relaxed)
+}
+
+pub fn switch_in(preempt_count: usize) {
+    local()
+        .preempt_count
+        .store(preempt_count, Ordering::Relaxed);
+}
+
+#[cfg(feature = "selftest")]
+pub fn preempt_test() {
+    use alloc::boxed::Box;
+    use alloc::sync::Arc;
+
+    use log::info;
+
+    use crate::selftest::{spin_until, wait_until};
+    use crate::smp::online_harts;
+    use crate::task::{add_task, current_task, TaskControlBlock};
+    use crate::timer::{get_time_us, TICK_US};
+
+    let outer = IrqGuard::new();
+    let inner = IrqGuard::new();
+    drop(inner);
+    assert!(!sstatus::read().sie());
+    drop(outer);
+    assert!(sstatus::read().sie());
+
+    // Two threads spinning on one hart, each only gets on with the other one preempting it.
+    let hart = online_harts().trailing_zeros() as usize;
+    let started = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+    let nivcsw = || {
+        current_task()
+            .unwrap()
+            .inner_exclusive_access()
+            .usage
+            .nivcsw
+    };
+    for _ in 0..2 {
+        let (started, done) = (started.clone(), done.clone());
+        let task = Arc::new(TaskControlBlock::new_kernel_thread(Box::new(move || {
+            started.fetch_add(1, Ordering::SeqCst);
+            preempt_disable();
+            let before = nivcsw();
+            let until = get_time_us() + 3 * TICK_US;
+            spin_until(|| get_time_us() >= until);
+            assert_eq!(nivcsw(), before);
+            preempt_enable();
+
+            spin_until(|| started.load(Ordering::SeqCst) == 2);
+            done.fetch_add(1, Ordering::SeqCst);
+        })));
+        task.inner_exclusive_access().affinity = 1 << hart;
+        add_task(task);
+    }
+    wait_until(|| done.load(Ordering::SeqCst) == 2);
+
+    info!("preempt_test passed !");
+}
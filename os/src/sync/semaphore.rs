@@ -0,0 +1,53 @@
+use super::{Interrupted, Sleeper, SpinLock, WaitQueue, WakeReason};
+
+/// A counting semaphore of user programs.
+pub struct Semaphore {
+    inner: SpinLock<SemaphoreInner>,
+}
+
+struct SemaphoreInner {
+    /// Resources left, or the number of waiting tasks if negative.
+    count: isize,
+    wait_queue: WaitQueue,
+}
+
+impl Semaphore {
+    pub fn new(count: usize) -> Self {
+        Self {
+            inner: SpinLock::new(SemaphoreInner {
+                count: count as isize,
+                wait_queue: WaitQueue::default(),
+            }),
+        }
+    }
+
+    /// Releases a resource, handing it to the task that waited longest if any.
+    pub fn up(&self) {
+        let mut inner = self.inner.lock();
+        inner.count += 1;
+        if inner.count <= 0 {
+            inner.wait_queue.wake_one();
+        }
+    }
+
+    /// Takes a resource, blocks until one is handed over if none is left.
+    pub fn down(&self) -> Result<(), Interrupted> {
+        let mut inner = self.inner.lock();
+        if inner.count > 0 {
+            inner.count -= 1;
+            return Ok(());
+        }
+        let sleeper = Sleeper::prepare(true)?;
+        inner.count -= 1;
+        inner.wait_queue.push(sleeper.clone());
+        drop(inner);
+        if sleeper.sleep() == WakeReason::Woken {
+            return Ok(());
+        }
+        // No longer waiting, a resource released meanwhile stays available.
+        let mut inner = self.inner.lock();
+        inner.wait_queue.remove(&sleeper);
+        inner.count += 1;
+        Err(Interrupted)
+    }
+}
@@ -0,0 +1,24 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::{Condvar, DeadlockDetector, Mutex, Semaphore, SpinLock};
+
+/// Mutexes, semaphores and condition variables of the tasks sharing an address space.
+pub struct SyncTable {
+    pub mutexes: Vec<Arc<dyn Mutex>>,
+    pub semaphores: Vec<Arc<Semaphore>>,
+    pub condvars: Vec<Arc<Condvar>>,
+    pub deadlock: DeadlockDetector,
+}
+
+impl SyncTable {
+    /// An empty table, for a freshly loaded program.
+    pub fn new() -> Arc<SpinLock<Self>> {
+        Arc::new(SpinLock::new(Self {
+            mutexes: Vec::new(),
+            semaphores: Vec::new(),
+            condvars: Vec::new(),
+            deadlock: DeadlockDetector::default(),
+        }))
+    }
+}
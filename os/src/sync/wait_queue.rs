@@ -0,0 +1,190 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use super::{preempt_disable, preempt_enable};
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+
+/// Returned by syscalls whose wait a signal interrupted, Linux's `-EINTR`.
+pub const EINTR: isize = -4;
+
+/// A signal arrived before what the task waited for.
+pub struct Interrupted;
+
+/// How a sleep ended.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WakeReason {
+    Woken = 1,
+    Interrupted,
+    TimedOut,
+}
+
+/// `Sleeper::state` until the sleep ends, then the `WakeReason`.
+const SLEEPING: u8 = 0;
+const WOKEN: u8 = WakeReason::Woken as u8;
+const TIMED_OUT: u8 = WakeReason::TimedOut as u8;
+
+/// One sleep of a task, ended by whoever wakes it up first.
+pub struct Sleeper {
+    task: Arc<TaskControlBlock>,
+    state: AtomicU8,
+}
+
+impl Sleeper {
+    /// Gets the running task ready to sleep, interruptible ones fail if a signal is pending.
+    /// Preemption stays disabled until `sleep`.
+    pub fn prepare(interruptible: bool) -> Result<Arc<Self>, Interrupted> {
+        let task = current_task().unwrap();
+        let sleeper = Arc::new(Self {
+            task: task.clone(),
+            state: AtomicU8::new(SLEEPING),
+        });
+        preempt_disable();
+        if interruptible {
+            let mut inner = task.inner_exclusive_access();
+            if inner.signals.interrupts() {
+                drop(inner);
+                preempt_enable();
+                return Err(Interrupted);
+            }
+            // From here on a signal wakes the task up, even if it did not block yet.
+            inner.sleeper = Some(sleeper.clone());
+        }
+        Ok(sleeper)
+    }
+
+    /// Blocks until the sleep ended, returns how.
+    pub fn sleep(&self) -> WakeReason {
+        block_current_and_run_next();
+        preempt_enable();
+        self.task.inner_exclusive_access().sleeper = None;
+        match self.state.load(Ordering::Acquire) {
+            WOKEN => WakeReason::Woken,
+            TIMED_OUT => WakeReason::TimedOut,
+            _ => WakeReason::Interrupted,
+        }
+    }
+
+    /// Ends the sleep for `reason` and wakes the task up, returns false if it ended already.
+    pub fn wake(&self, reason: WakeReason) -> bool {
+        if self
+            .state
+            .compare_exchange(SLEEPING, reason as u8, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            return false;
+        }
+        wakeup_task(self.task.clone());
+        true
+    }
+}
+
+/// Tasks sleeping until someone wakes them up, in the order they went to sleep.
+#[derive(Default)]
+pub struct WaitQueue {
+    sleepers: VecDeque<Arc<Sleeper>>,
+}
+
+impl WaitQueue {
+    pub fn push(&mut self, sleeper: Arc<Sleeper>) {
+        self.sleepers.push_back(sleeper);
+    }
+
+    /// Takes out a sleeper a signal woke up, if it is still queued.
+    pub fn remove(&mut self, sleeper: &Arc<Sleeper>) {
+        self.sleepers.retain(|other| !Arc::ptr_eq(other, sleeper));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<TaskControlBlock>> {
+        self.sleepers.iter().map(|sleeper| &sleeper.task)
+    }
+
+    /// Wakes up the task that waited longest, returns it unless none was waiting.
+    pub fn wake_one(&mut self) -> Option<Arc<TaskControlBlock>> {
+        while let Some(sleeper) = self.sleepers.pop_front() {
+            if sleeper.wake(WakeReason::Woken) {
+                return Some(sleeper.task.clone());
+            }
+        }
+        None
+    }
+
+    /// Wakes up all waiting tasks, for when each has to check for itself whether it can go on.
+    pub fn wake_all(&mut self) {
+        while self.wake_one().is_some() {}
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn wait_queue_test() {
+    use core::sync::atomic::AtomicUsize;
+
+    use log::info;
+
+    use super::{Condvar, Mutex, MutexBlocking, MutexSpin, Semaphore};
+    use crate::selftest::wait_until;
+    use crate::task::{send_signal, spawn_kernel_thread, SIGINT};
+
+    let sleeping = |task: &Arc<TaskControlBlock>| task.inner_exclusive_access().sleeper.is_some();
+    let step = Arc::new(AtomicUsize::new(0));
+
+    let spin = MutexSpin::new();
+    assert!(spin.lock().is_ok());
+    assert!(spin.unlock());
+    assert!(!spin.unlock());
+
+    // A blocking mutex is handed to the waiter, only its holder can unlock it.
+    let mutex = Arc::new(MutexBlocking::new());
+    assert!(mutex.lock().is_ok());
+    let (thread_mutex, thread_step) = (mutex.clone(), step.clone());
+    let task = spawn_kernel_thread(move || {
+        assert!(thread_mutex.lock().is_ok());
+        thread_step.store(1, Ordering::SeqCst);
+        assert!(thread_mutex.unlock());
+    });
+    wait_until(|| sleeping(&task));
+    assert_eq!(step.load(Ordering::SeqCst), 0);
+    assert!(mutex.unlock());
+    wait_until(|| step.load(Ordering::SeqCst) == 1);
+    assert!(!mutex.unlock());
+
+    // A resource released is handed to the waiter, one taken back by a signal stays available.
+    let sem = Arc::new(Semaphore::new(1));
+    assert!(sem.down().is_ok());
+    let (thread_sem, thread_step) = (sem.clone(), step.clone());
+    let task = spawn_kernel_thread(move || {
+        assert!(thread_sem.down().is_ok());
+        thread_step.store(2, Ordering::SeqCst);
+        assert!(thread_sem.down().is_err());
+        thread_step.store(3, Ordering::SeqCst);
+    });
+    wait_until(|| sleeping(&task));
+    sem.up();
+    wait_until(|| step.load(Ordering::SeqCst) == 2 && sleeping(&task));
+    send_signal(&task, SIGINT);
+    wait_until(|| step.load(Ordering::SeqCst) == 3);
+    sem.up();
+    assert!(sem.down().is_ok());
+
+    // Waiting needs the mutex held, which is released meanwhile.
+    let condvar = Arc::new(Condvar::new());
+    assert!(matches!(condvar.wait(mutex.as_ref(), || {}), Ok(false)));
+    let (thread_condvar, thread_mutex, thread_step) =
+        (condvar.clone(), mutex.clone(), step.clone());
+    spawn_kernel_thread(move || {
+        assert!(thread_mutex.lock().is_ok());
+        let released = || thread_step.store(4, Ordering::SeqCst);
+        assert!(matches!(
+            thread_condvar.wait(thread_mutex.as_ref(), released),
+            Ok(true)
+        ));
+        thread_step.store(5, Ordering::SeqCst);
+    });
+    wait_until(|| step.load(Ordering::SeqCst) == 4);
+    assert!(mutex.lock().is_ok());
+    condvar.signal();
+    assert!(mutex.unlock());
+    wait_until(|| step.load(Ordering::SeqCst) == 5);
+
+    info!("wait_queue_test passed !");
+}
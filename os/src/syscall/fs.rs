@@ -1,52 +1,116 @@
-use core::str;
+use alloc::sync::Arc;
 
-use crate::mm::translated_byte_buffer;
-use crate::print;
-use crate::sbi::console_getchar;
-use crate::task::{current_user_token, suspend_current_and_run_next};
+use crate::fs::{make_pipe, File};
+use crate::mm::{translated_byte_buffer, translated_mut, translated_ref};
+use crate::task::{current_task, current_user_token, pgrp_in_session, RLIMIT_NOFILE};
+use crate::tty::{foreground_pgrp, set_foreground_pgrp};
 
-const FD_STDIN: usize = 0;
-const FD_STDOUT: usize = 1;
+/// File the descriptor `fd` of the running task refers to.
+fn current_file(fd: usize) -> Option<Arc<dyn File>> {
+    let fd_table = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .fd_table
+        .clone();
+    let file = fd_table.lock().get(fd);
+    file
+}
 
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
-            for buffer in buffers {
-                print!("{}", str::from_utf8(buffer).unwrap());
-            }
-            len as isize
-        }
-        _ => panic!("Unsupported fd in sys_write"),
+/// Opens `files` at the lowest free descriptors of the running task, all or none of them.
+fn alloc_fds<const N: usize>(files: [Arc<dyn File>; N]) -> Option<[usize; N]> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let limit = inner.rlimits.cur(RLIMIT_NOFILE);
+    let fd_table = inner.fd_table.clone();
+    drop(inner);
+    let mut fd_table = fd_table.lock();
+    let mut fds = [0; N];
+    for (i, file) in files.into_iter().enumerate() {
+        let Some(fd) = fd_table.alloc(file, limit) else {
+            fds[..i].iter().for_each(|&fd| drop(fd_table.close(fd)));
+            return None;
+        };
+        fds[i] = fd;
     }
+    Some(fds)
 }
 
-pub fn sys_read(fd: usize, buffer: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDIN => {
-            assert_eq!(len, 1, "Only support len = 1 in sys_read");
-            let mut c;
-
-            loop {
-                c = console_getchar();
-                if c == 0 {
-                    suspend_current_and_run_next();
-                    continue;
-                } else {
-                    break;
-                }
-            }
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+    let Some(file) = current_file(fd).filter(|file| file.writable()) else {
+        return -1;
+    };
+    file.write(translated_byte_buffer(current_user_token(), buf, len))
+}
 
-            let ch = c as u8;
-            let mut buffers = translated_byte_buffer(current_user_token(), buffer, len);
+pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
+    let Some(file) = current_file(fd).filter(|file| file.readable()) else {
+        return -1;
+    };
+    file.read(translated_byte_buffer(current_user_token(), buf, len))
+}
 
-            unsafe {
-                buffers[0].as_mut_ptr().write_volatile(ch);
-            }
-            1
+/// Creates a pipe and stores the descriptors of its read end and its write end at `pipe`.
+pub fn sys_pipe(pipe: *mut i32) -> isize {
+    let (read_end, write_end) = make_pipe();
+    let Some([read_fd, write_fd]) = alloc_fds([read_end, write_end]) else {
+        return -1;
+    };
+    let token = current_user_token();
+    *translated_mut(token, pipe) = read_fd as i32;
+    *translated_mut(token, pipe.wrapping_add(1)) = write_fd as i32;
+    0
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    let fd_table = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .fd_table
+        .clone();
+    let Some(file) = fd_table.lock().close(fd) else {
+        return -1;
+    };
+    // Dropped with the table released, closing a pipe end wakes up tasks.
+    drop(file);
+    0
+}
+
+/// Opens the file `fd` refers to at the lowest free descriptor too, returns that one.
+pub fn sys_dup(fd: usize) -> isize {
+    let Some(file) = current_file(fd) else {
+        return -1;
+    };
+    match alloc_fds([file]) {
+        Some([new_fd]) => new_fd as isize,
+        None => -1,
+    }
+}
+
+/// Gets the foreground process group of the terminal.
+const TIOCGPGRP: usize = 0x540f;
+/// Sets the foreground process group of the terminal.
+const TIOCSPGRP: usize = 0x5410;
+
+/// Gets or sets the foreground process group of the console at `arg`.
+pub fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
+    if !current_file(fd).is_some_and(|file| file.is_tty()) {
+        return -1;
+    }
+    let token = current_user_token();
+    match cmd {
+        TIOCGPGRP => {
+            *translated_mut(token, arg as *mut i32) = foreground_pgrp() as i32;
+            0
         }
-        _ => {
-            panic!("Unsupported fd in sys_read");
+        TIOCSPGRP => {
+            let pgid = *translated_ref(token, arg as *const i32);
+            let sid = current_task().unwrap().inner_exclusive_access().sid;
+            if pgid < 0 || !pgrp_in_session(pgid as usize, sid) {
+                return -1;
+            }
+            set_foreground_pgrp(pgid as usize);
+            0
         }
+        _ => -1,
     }
 }
@@ -1,48 +1,161 @@
-use self::fs::{sys_read, sys_write};
+use self::fs::{sys_close, sys_dup, sys_ioctl, sys_pipe, sys_read, sys_write};
 use self::process::{
-    sys_exec, sys_exit, sys_fork, sys_get_pid, sys_get_time, sys_mmap, sys_munmap, sys_sbrk,
-    sys_set_priority, sys_spawn, sys_task_info, sys_waitpid, sys_yield,
+    sys_clone, sys_exec, sys_exit, sys_get_pid, sys_get_time, sys_getegid, sys_geteuid, sys_getgid,
+    sys_getpgid, sys_getresgid, sys_getresuid, sys_getrlimit, sys_getrusage, sys_getsid,
+    sys_getuid, sys_kill, sys_mmap, sys_munmap, sys_sbrk, sys_sched_getaffinity,
+    sys_sched_queue_lens, sys_sched_setaffinity, sys_seccomp, sys_set_deadline, sys_set_priority,
+    sys_setgid, sys_setpgid, sys_setpriority, sys_setregid, sys_setresgid, sys_setresuid,
+    sys_setreuid, sys_setrlimit, sys_setsid, sys_setuid, sys_spawn, sys_task_info, sys_waitpid,
+    sys_waitpid_rusage, sys_yield,
 };
-use crate::task::current_task;
+use self::ptrace::sys_ptrace;
+use self::sync::{
+    sys_condvar_create, sys_condvar_signal, sys_condvar_wait, sys_enable_deadlock_detect,
+    sys_futex, sys_mutex_create, sys_mutex_lock, sys_mutex_stats, sys_mutex_unlock,
+    sys_semaphore_create, sys_semaphore_down, sys_semaphore_up,
+};
+use crate::task::{current_task, exit_current_and_run_next, FilterAction, SIGSYS};
 // use crate::task::inc_syscall_times;
 
 mod fs;
 mod process;
+mod ptrace;
+mod sync;
 
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_IOCTL: usize = 29;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_FUTEX: usize = 98;
+const SYSCALL_PTRACE: usize = 117;
+const SYSCALL_SCHED_SETAFFINITY: usize = 122;
+const SYSCALL_SCHED_GETAFFINITY: usize = 123;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_KILL: usize = 129;
 const SYSCALL_SET_PRIORITY: usize = 140;
+const SYSCALL_SETREGID: usize = 143;
+const SYSCALL_SETGID: usize = 144;
+const SYSCALL_SETREUID: usize = 145;
+const SYSCALL_SETUID: usize = 146;
+const SYSCALL_SETRESUID: usize = 147;
+const SYSCALL_GETRESUID: usize = 148;
+const SYSCALL_SETRESGID: usize = 149;
+const SYSCALL_GETRESGID: usize = 150;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETSID: usize = 156;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GET_PID: usize = 172;
+const SYSCALL_GETUID: usize = 174;
+const SYSCALL_GETEUID: usize = 175;
+const SYSCALL_GETGID: usize = 176;
+const SYSCALL_GETEGID: usize = 177;
 const SYSCALL_SBRK: usize = 214;
 const SYSCALL_MUNMAP: usize = 215;
-const SYSCALL_FORK: usize = 220;
+const SYSCALL_CLONE: usize = 220;
 const SYSCALL_EXEC: usize = 221;
 const SYSCALL_MMAP: usize = 222;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_SECCOMP: usize = 277;
 const SYSCALL_SPAWN: usize = 400;
 const SYSCALL_TASK_INFO: usize = 410;
+const SYSCALL_SCHED_QUEUE_LENS: usize = 411;
+const SYSCALL_SET_DEADLINE: usize = 412;
+const SYSCALL_WAITPID_RUSAGE: usize = 413;
+const SYSCALL_SETPRIORITY: usize = 414;
+const SYSCALL_ENABLE_DEADLOCK_DETECT: usize = 469;
+const SYSCALL_MUTEX_CREATE: usize = 1010;
+const SYSCALL_MUTEX_LOCK: usize = 1011;
+const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_MUTEX_STATS: usize = 1013;
+const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
+const SYSCALL_SEMAPHORE_UP: usize = 1021;
+const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_CONDVAR_CREATE: usize = 1030;
+const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
+const SYSCALL_CONDVAR_WAIT: usize = 1032;
+
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
+    let task = current_task().unwrap();
+    task.record_syscall_times(syscall_id);
+    let action = task
+        .inner_exclusive_access()
+        .filters
+        .check(syscall_id, &args);
+    drop(task);
+    match action {
+        FilterAction::Allow => {}
+        FilterAction::Error => return -1,
+        FilterAction::Kill => exit_current_and_run_next(-(SIGSYS as i32)),
+    }
 
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
-    current_task().unwrap().record_syscall_times(syscall_id);
     match syscall_id {
+        SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_IOCTL => sys_ioctl(args[0], args[1], args[2]),
+        SYSCALL_CLOSE => sys_close(args[0]),
+        SYSCALL_PIPE => sys_pipe(args[0] as _),
         SYSCALL_READ => sys_read(args[0], args[1] as _, args[2]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as _, args[2]),
         SYSCALL_EXIT => sys_exit(args[0] as _),
+        SYSCALL_FUTEX => sys_futex(args[0], args[1], args[2], args[3], args[4]),
+        SYSCALL_PTRACE => sys_ptrace(args[0], args[1], args[2], args[3]),
+        SYSCALL_SCHED_SETAFFINITY => sys_sched_setaffinity(args[0], args[1], args[2] as _),
+        SYSCALL_SCHED_GETAFFINITY => sys_sched_getaffinity(args[0], args[1], args[2] as _),
         SYSCALL_YIELD => sys_yield(),
+        SYSCALL_KILL => sys_kill(args[0] as _, args[1]),
         SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as _),
+        SYSCALL_SETREGID => sys_setregid(args[0] as _, args[1] as _),
+        SYSCALL_SETGID => sys_setgid(args[0] as _),
+        SYSCALL_SETREUID => sys_setreuid(args[0] as _, args[1] as _),
+        SYSCALL_SETUID => sys_setuid(args[0] as _),
+        SYSCALL_SETRESUID => sys_setresuid(args[0] as _, args[1] as _, args[2] as _),
+        SYSCALL_GETRESUID => sys_getresuid(args[0] as _, args[1] as _, args[2] as _),
+        SYSCALL_SETRESGID => sys_setresgid(args[0] as _, args[1] as _, args[2] as _),
+        SYSCALL_GETRESGID => sys_getresgid(args[0] as _, args[1] as _, args[2] as _),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1] as _),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
+        SYSCALL_GETSID => sys_getsid(args[0]),
+        SYSCALL_SETSID => sys_setsid(),
+        SYSCALL_GETRLIMIT => sys_getrlimit(args[0], args[1] as _),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], args[1] as _),
+        SYSCALL_GETRUSAGE => sys_getrusage(args[0] as _, args[1] as _),
         SYSCALL_GET_TIME => sys_get_time(args[0] as _, args[1]),
         SYSCALL_GET_PID => sys_get_pid(),
+        SYSCALL_GETUID => sys_getuid(),
+        SYSCALL_GETEUID => sys_geteuid(),
+        SYSCALL_GETGID => sys_getgid(),
+        SYSCALL_GETEGID => sys_getegid(),
         SYSCALL_SBRK => sys_sbrk(args[0] as _),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
         SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
-        SYSCALL_FORK => sys_fork(),
+        SYSCALL_CLONE => sys_clone(args[0], args[1], args[2] as _, args[3], args[4] as _),
         SYSCALL_EXEC => sys_exec(args[0] as _),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as _, args[1] as _),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as _, args[1] as _, args[2]),
+        SYSCALL_SECCOMP => sys_seccomp(args[0] as _, args[1], args[2]),
         SYSCALL_SPAWN => sys_spawn(args[0] as _),
         SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut _),
+        SYSCALL_SCHED_QUEUE_LENS => sys_sched_queue_lens(args[0] as *mut _, args[1]),
+        SYSCALL_SET_DEADLINE => sys_set_deadline(args[0], args[1], args[2]),
+        SYSCALL_WAITPID_RUSAGE => sys_waitpid_rusage(args[0] as _, args[1] as _, args[2] as _),
+        SYSCALL_SETPRIORITY => sys_setpriority(args[0], args[1] as _),
+        SYSCALL_ENABLE_DEADLOCK_DETECT => sys_enable_deadlock_detect(args[0]),
+        SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] != 0),
+        SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
+        SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
+        SYSCALL_MUTEX_STATS => sys_mutex_stats(args[0], args[1] as _),
+        SYSCALL_SEMAPHORE_CREATE => sys_semaphore_create(args[0]),
+        SYSCALL_SEMAPHORE_UP => sys_semaphore_up(args[0]),
+        SYSCALL_SEMAPHORE_DOWN => sys_semaphore_down(args[0]),
+        SYSCALL_CONDVAR_CREATE => sys_condvar_create(),
+        SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
+        SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
         syscall_id => unreachable!("Unsupported syscall {}", syscall_id),
     }
 }
@@ -1,22 +1,79 @@
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::size_of;
 
 use log::info;
 
+use crate::config::{ALL_HARTS_MASK, PAGE_SIZE};
 use crate::loader::get_app_data_by_name;
-use crate::mm::{translated_mut, translated_str};
+use crate::mm::{translated_mut, translated_ref, translated_str};
+use crate::smp::{hart_id, online_harts};
+use crate::sync::{preempt_disable, preempt_enable, priority_changed};
 use crate::task::{
-    add_task, current_task, current_user_token, exit_current_and_run_next,
-    suspend_current_and_run_next, TaskInfo,
+    add_task, all_tasks, block_current_and_run_next, current_task, current_user_token,
+    exit_current_and_run_next, insert_into_pid2task, pgrp_in_session, pid2task, ptrace_exec_trap,
+    sched_queue_lens, send_signal, set_current_deadline, suspend_current_and_run_next,
+    tasks_in_pgrp, CloneFlags, FilterRule, IdSet, RLimit, ResourceUsage, SyscallFilter,
+    TaskControlBlock, TaskInfo, CSIGNAL, NSIG, RLIMIT_NPROC, SIGCONT,
 };
 use crate::timer::get_time_us;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct TimeVal {
     pub sec: usize,
     pub usec: usize,
 }
 
+impl TimeVal {
+    fn from_us(us: usize) -> Self {
+        Self {
+            sec: us / 1_000_000,
+            usec: us % 1_000_000,
+        }
+    }
+}
+
+/// Layout of Linux's `struct rusage`, the fields this kernel does not track stay 0.
+#[repr(C)]
+#[derive(Default)]
+pub struct Rusage {
+    pub utime: TimeVal,
+    pub stime: TimeVal,
+    /// Peak resident set in KiB.
+    pub maxrss: isize,
+    pub ixrss: isize,
+    pub idrss: isize,
+    pub isrss: isize,
+    pub minflt: isize,
+    pub majflt: isize,
+    pub nswap: isize,
+    pub inblock: isize,
+    pub oublock: isize,
+    pub msgsnd: isize,
+    pub msgrcv: isize,
+    pub nsignals: isize,
+    pub nvcsw: isize,
+    pub nivcsw: isize,
+}
+
+impl From<&ResourceUsage> for Rusage {
+    fn from(usage: &ResourceUsage) -> Self {
+        Self {
+            utime: TimeVal::from_us(usage.utime),
+            stime: TimeVal::from_us(usage.stime),
+            maxrss: (usage.max_rss_pages * PAGE_SIZE / 1024) as isize,
+            minflt: usage.page_faults as isize,
+            nvcsw: usage.nvcsw as isize,
+            nivcsw: usage.nivcsw as isize,
+            ..Default::default()
+        }
+    }
+}
+
+const RUSAGE_SELF: isize = 0;
+const RUSAGE_CHILDREN: isize = -1;
+
 pub fn sys_exit(exit_code: i32) -> ! {
     info!(
         "[kernel] pid[{}] Application exit with code {}",
@@ -28,20 +85,64 @@ pub fn sys_exit(exit_code: i32) -> ! {
 }
 
 pub fn sys_yield() -> isize {
+    // For a real-time task yielding means its current job is done.
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .sched
+        .complete_rt_job(get_time_us());
     suspend_current_and_run_next();
     0
 }
 
 pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     let ts = translated_mut(current_user_token(), ts);
-    let us = get_time_us();
-    *ts = TimeVal {
-        sec: us / 1_000_000,
-        usec: us % 1_000_000,
+    *ts = TimeVal::from_us(get_time_us());
+    0
+}
+
+/// Copies the resource usage of the caller (`RUSAGE_SELF`) or of its reaped children
+/// (`RUSAGE_CHILDREN`) to `usage`.
+pub fn sys_getrusage(who: isize, usage: *mut Rusage) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let rusage = match who {
+        RUSAGE_SELF => {
+            // Charge the time of this very syscall so far.
+            inner.usage.charge_kernel(get_time_us());
+            Rusage::from(&inner.usage)
+        }
+        RUSAGE_CHILDREN => Rusage::from(&inner.children_usage),
+        _ => return -1,
     };
+    *translated_mut(inner.get_user_token(), usage) = rusage;
     0
 }
 
+/// Copies the limit of `resource` to `rlim`.
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let Some(limit) = inner.rlimits.get(resource) else {
+        return -1;
+    };
+    *translated_mut(inner.get_user_token(), rlim) = limit;
+    0
+}
+
+/// Sets the limit of `resource` to the one at `rlim`.
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let limit = *translated_ref(inner.get_user_token(), rlim);
+    let privileged = inner.cred.is_privileged();
+    if inner.rlimits.set(resource, limit, privileged) {
+        0
+    } else {
+        -1
+    }
+}
+
 pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
     let ti = translated_mut(current_user_token(), ti);
     let task_info = current_task().unwrap().get_taskinfo();
@@ -49,6 +150,17 @@ pub fn sys_task_info(ti: *mut TaskInfo) -> isize {
     0
 }
 
+/// Copies the number of ready tasks in each queue of the scheduler to `buf`, at most `len` of
+/// them, and returns the number of queues.
+pub fn sys_sched_queue_lens(buf: *mut usize, len: usize) -> isize {
+    let token = current_user_token();
+    let lens = sched_queue_lens();
+    for (i, queue_len) in lens.iter().take(len).enumerate() {
+        *translated_mut(token, unsafe { buf.add(i) }) = *queue_len;
+    }
+    lens.len() as isize
+}
+
 pub fn sys_sbrk(size: i32) -> isize {
     if let Some(old_brk) = current_task().unwrap().change_program_brk(size) {
         old_brk as isize
@@ -87,36 +199,182 @@ pub fn sys_get_pid() -> isize {
 
 pub fn sys_spawn(path: *const u8) -> isize {
     let path = translated_str(current_user_token(), path);
-    if let Some(elf_data) = get_app_data_by_name(path.as_str()) {
-        let task = current_task().unwrap();
-        let new_task = task.spawn(elf_data);
-        new_task.pid.0 as isize
+    let task = current_task().unwrap();
+    if exceeds_nproc_limit(&task) {
+        return -1;
+    }
+    if let Some(new_task) =
+        get_app_data_by_name(path.as_str()).and_then(|elf| task.spawn(&path, elf))
+    {
+        let new_pid = new_task.getpid();
+        insert_into_pid2task(new_pid, new_task.clone());
+        add_task(new_task);
+        new_pid as isize
     } else {
         -1
     }
 }
 
+/// Sets the priority of the calling task.
 pub fn sys_set_priority(prio: isize) -> isize {
-    if prio >= 2 {
-        current_task()
-            .unwrap()
-            .inner_exclusive_access()
-            .set_priority(prio);
-        prio
+    sys_setpriority(0, prio)
+}
+
+/// Sets the priority of the task `pid`, 0 meaning the caller.
+pub fn sys_setpriority(pid: usize, prio: isize) -> isize {
+    if prio < 2 {
+        return -1;
+    }
+    let Some(task) = task_by_pid(pid) else {
+        return -1;
+    };
+    let cred = current_task().unwrap().inner_exclusive_access().cred;
+    let mut inner = task.inner_exclusive_access();
+    if !cred.can_manage(&inner.cred)
+        || (!cred.is_privileged() && prio > inner.sched.base_priority as isize)
+    {
+        return -1;
+    }
+    inner.set_priority(prio);
+    drop(inner);
+    priority_changed(&task);
+    prio
+}
+
+/// Makes the calling task a real-time task that needs `runtime` microseconds of CPU in every
+/// `period`, each within `deadline` of the start of the period. A `runtime` of 0 makes it a normal
+/// task again.
+pub fn sys_set_deadline(runtime: usize, period: usize, deadline: usize) -> isize {
+    if set_current_deadline(runtime, period, deadline) {
+        0
     } else {
         -1
     }
 }
 
-pub fn sys_fork() -> isize {
+/// Task with the given pid, 0 meaning the caller.
+fn task_by_pid(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    if pid == 0 {
+        current_task()
+    } else {
+        pid2task(pid)
+    }
+}
+
+/// Restricts the task `pid` to the harts set in the mask at `mask`, `len` being its size in bytes.
+pub fn sys_sched_setaffinity(pid: usize, len: usize, mask: *const usize) -> isize {
+    if len < size_of::<usize>() {
+        return -1;
+    }
+    let mask = *translated_ref(current_user_token(), mask) & ALL_HARTS_MASK;
+    if mask & online_harts() == 0 {
+        return -1;
+    }
+    let Some(task) = task_by_pid(pid) else {
+        return -1;
+    };
+    let cred = current_task().unwrap().inner_exclusive_access().cred;
+    let mut inner = task.inner_exclusive_access();
+    if !cred.can_manage(&inner.cred) {
+        return -1;
+    }
+    // A real-time task can't leave the hart its bandwidth is reserved on.
+    if inner.sched.rt.is_some_and(|rt| mask & (1 << rt.hart) == 0) {
+        return -1;
+    }
+    inner.affinity = mask;
+    drop(inner);
+
+    // Other tasks move when they are next scheduled or ticked, the caller right away.
+    let hart = hart_id();
+    if Arc::ptr_eq(&task, &current_task().unwrap()) && !task.can_run_on(hart) {
+        drop(task);
+        suspend_current_and_run_next();
+    }
+    0
+}
+
+/// Copies the affinity mask of the task `pid` to `mask`, returns the size of the mask in bytes.
+pub fn sys_sched_getaffinity(pid: usize, len: usize, mask: *mut usize) -> isize {
+    if len < size_of::<usize>() {
+        return -1;
+    }
+    let Some(task) = task_by_pid(pid) else {
+        return -1;
+    };
+    let affinity = task.inner_exclusive_access().affinity;
+    *translated_mut(current_user_token(), mask) = affinity;
+    size_of::<usize>() as isize
+}
+
+/// Whether `task` is at its `RLIMIT_NPROC`.
+fn exceeds_nproc_limit(task: &Arc<TaskControlBlock>) -> bool {
+    let inner = task.inner_exclusive_access();
+    let uid = inner.cred.uid.real;
+    let limit = inner.rlimits.cur(RLIMIT_NPROC);
+    drop(inner);
+
+    let count = all_tasks()
+        .iter()
+        .filter(|task| task.inner_exclusive_access().cred.uid.real == uid)
+        .count();
+    count >= limit
+}
+
+/// Creates a child sharing with the caller what `flags` asks for, `fork` with `flags` 0. The child
+/// starts on `stack` unless it is 0.
+pub fn sys_clone(
+    flags: usize,
+    stack: usize,
+    parent_tid: *mut i32,
+    tls: usize,
+    child_tid: *mut i32,
+) -> isize {
+    // Parents learn of exited children through `waitpid` only, no signal is sent on exit, so the
+    // exit signal in the low byte is ignored.
+    let Some(flags) = CloneFlags::from_bits(flags & !CSIGNAL) else {
+        return -1;
+    };
+    if !flags.is_valid() {
+        return -1;
+    }
     let current_task = current_task().unwrap();
-    let new_task = current_task.fork();
-    let new_pid = new_task.pid.0;
+    if exceeds_nproc_limit(&current_task) {
+        return -1;
+    }
+    // The caller's parent may have exited already, it can't take a sibling then.
+    let parent = if flags.contains(CloneFlags::PARENT) {
+        let parent = current_task.inner_exclusive_access().parent.clone();
+        match parent.and_then(|parent| parent.upgrade()) {
+            Some(parent) => parent,
+            None => return -1,
+        }
+    } else {
+        current_task.clone()
+    };
 
-    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
+    let new_task = current_task.clone_task(&parent, flags, stack, tls);
+    let new_pid = new_task.pid.0;
+    if flags.contains(CloneFlags::PARENT_SETTID) {
+        *translated_mut(current_user_token(), parent_tid) = new_pid as i32;
+    }
+    if flags.contains(CloneFlags::CHILD_SETTID) {
+        let token = new_task.inner_exclusive_access().get_user_token();
+        *translated_mut(token, child_tid) = new_pid as i32;
+    }
+    insert_into_pid2task(new_pid, new_task.clone());
 
-    trap_cx.x[10] = 0;
-    add_task(new_task);
+    if flags.contains(CloneFlags::VFORK) {
+        // Registered before the child can run, it wakes the caller exactly once.
+        preempt_disable();
+        new_task.inner_exclusive_access().vfork_parent = Some(current_task.clone());
+        add_task(new_task);
+        drop(current_task);
+        block_current_and_run_next();
+        preempt_enable();
+    } else {
+        add_task(new_task);
+    }
     new_pid as isize
 }
 
@@ -125,14 +383,38 @@ pub fn sys_exec(path: *const u8) -> isize {
     let path = translated_str(token, path);
     if let Some(data) = get_app_data_by_name(path.as_str()) {
         let task = current_task().unwrap();
-        task.exec(data);
-        0
+        if task.exec(&path, data) {
+            ptrace_exec_trap();
+            0
+        } else {
+            -1
+        }
     } else {
         -1
     }
 }
 
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// `waitpid` option: also report children that stopped.
+const WUNTRACED: usize = 2;
+
+/// Reaps the exited child `pid`, or any child if -1, and stores its exit code at `exit_code_ptr`.
+/// Stopped children are reported once if traced or with `WUNTRACED`. Returns -2 while the children
+/// are still running.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> isize {
+    wait_child(pid, exit_code_ptr, options, None)
+}
+
+/// `sys_waitpid` also copying the usage of the reaped child and its reaped children to `rusage`.
+pub fn sys_waitpid_rusage(pid: isize, exit_code_ptr: *mut i32, rusage: *mut Rusage) -> isize {
+    wait_child(pid, exit_code_ptr, 0, Some(rusage))
+}
+
+fn wait_child(
+    pid: isize,
+    exit_code_ptr: *mut i32,
+    options: usize,
+    rusage: Option<*mut Rusage>,
+) -> isize {
     let task = current_task().unwrap();
 
     let mut inner = task.inner_exclusive_access();
@@ -145,8 +427,12 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
         return -1;
     }
 
+    // A zombie is only reaped once the hart it exited on dropped its reference, before that it
+    // may still be running on its kernel stack.
     let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
+        p.inner_exclusive_access().is_zombie()
+            && Arc::strong_count(p) == 1
+            && (pid == -1 || pid as usize == p.getpid())
     });
 
     if let Some((idx, _)) = pair {
@@ -154,11 +440,281 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
 
         assert_eq!(Arc::strong_count(&child), 1);
         let found_pid = child.getpid();
-        let exit_code = child.inner_exclusive_access().exit_code;
+        let child_inner = child.inner_exclusive_access();
+        let exit_code = child_inner.exit_code;
+        let mut child_usage = child_inner.usage;
+        child_usage.accumulate(&child_inner.children_usage);
+        drop(child_inner);
 
-        *translated_mut(inner.memory_set.token(), exit_code_ptr) = exit_code;
+        inner.children_usage.accumulate(&child_usage);
+        let token = inner.get_user_token();
+        *translated_mut(token, exit_code_ptr) = exit_code;
+        if let Some(rusage) = rusage {
+            *translated_mut(token, rusage) = Rusage::from(&child_usage);
+        }
+        found_pid as isize
+    } else {
+        let stopped = inner.children.iter().find_map(|p| {
+            if pid != -1 && pid as usize != p.getpid() {
+                return None;
+            }
+            let mut child_inner = p.inner_exclusive_access();
+            if let Some(trace) = child_inner.ptrace.as_mut() {
+                if let (Some(why), false) = (trace.stop, trace.stop_reported) {
+                    trace.stop_reported = true;
+                    return Some((p.getpid(), why));
+                }
+            }
+            if options & WUNTRACED == 0 || child_inner.stop_reported {
+                return None;
+            }
+            let sig = child_inner.stop_signal?;
+            child_inner.stop_reported = true;
+            Some((p.getpid(), sig))
+        });
+        let Some((found_pid, sig)) = stopped else {
+            return -2;
+        };
+        *translated_mut(inner.get_user_token(), exit_code_ptr) = (sig << 8 | 0x7f) as i32;
         found_pid as isize
+    }
+}
+
+/// Moves the task `pid`, 0 meaning the caller, into the process group `pgid`, 0 meaning its pid.
+pub fn sys_setpgid(pid: usize, pgid: isize) -> isize {
+    if pgid < 0 {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let target = if pid == 0 || pid == task.getpid() {
+        task.clone()
     } else {
-        -2
+        let child = task
+            .inner_exclusive_access()
+            .children
+            .iter()
+            .find(|child| child.getpid() == pid)
+            .cloned();
+        let Some(child) = child else {
+            return -1;
+        };
+        child
+    };
+    let target_pid = target.getpid();
+    let pgid = if pgid == 0 { target_pid } else { pgid as usize };
+
+    let sid = task.inner_exclusive_access().sid;
+    let target_sid = target.inner_exclusive_access().sid;
+    if target_sid != sid || target_sid == target_pid {
+        return -1;
     }
+    if pgid != target_pid && !pgrp_in_session(pgid, sid) {
+        return -1;
+    }
+    target.inner_exclusive_access().pgid = pgid;
+    0
+}
+
+pub fn sys_getpgid(pid: usize) -> isize {
+    match task_by_pid(pid) {
+        Some(task) => task.inner_exclusive_access().pgid as isize,
+        None => -1,
+    }
+}
+
+/// Makes the caller the leader of a new session and process group, unless it leads a group
+/// already. Returns the new session id.
+pub fn sys_setsid() -> isize {
+    let task = current_task().unwrap();
+    let pid = task.getpid();
+    let mut inner = task.inner_exclusive_access();
+    if inner.pgid == pid {
+        return -1;
+    }
+    inner.pgid = pid;
+    inner.sid = pid;
+    pid as isize
+}
+
+pub fn sys_getsid(pid: usize) -> isize {
+    match task_by_pid(pid) {
+        Some(task) => task.inner_exclusive_access().sid as isize,
+        None => -1,
+    }
+}
+
+/// Sends `sig` to the task `pid`, to the caller's process group if 0, to all other tasks if -1 and
+/// to the group `-pid` otherwise. A `sig` of 0 only checks that there is such a task.
+pub fn sys_kill(pid: isize, sig: usize) -> isize {
+    if sig >= NSIG {
+        return -1;
+    }
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let (cred, sid) = (inner.cred, inner.sid);
+    drop(inner);
+    let targets: Vec<_> = match pid {
+        0 => tasks_in_pgrp(task.inner_exclusive_access().pgid),
+        -1 => all_tasks()
+            .into_iter()
+            .filter(|target| !Arc::ptr_eq(target, &task))
+            .collect(),
+        pid if pid < 0 => tasks_in_pgrp(pid.unsigned_abs()),
+        pid => pid2task(pid as usize).into_iter().collect(),
+    };
+    // Anybody can continue a task of the same session, as job control does.
+    let targets: Vec<_> = targets
+        .into_iter()
+        .filter(|target| {
+            let target_inner = target.inner_exclusive_access();
+            cred.can_signal(&target_inner.cred) || (sig == SIGCONT && target_inner.sid == sid)
+        })
+        .collect();
+    if targets.is_empty() {
+        return -1;
+    }
+    if sig != 0 {
+        for target in targets.iter() {
+            send_signal(target, sig);
+        }
+    }
+    0
+}
+
+/// Installs a filter of the `len` rules at `rules` for the caller and its future children.
+pub fn sys_seccomp(rules: *const FilterRule, len: usize, default_action: usize) -> isize {
+    let token = current_user_token();
+    let rules = (0..len).map(|i| *translated_ref(token, rules.wrapping_add(i)));
+    let Some(filter) = SyscallFilter::parse(rules, default_action) else {
+        return -1;
+    };
+    let task = current_task().unwrap();
+    if task.inner_exclusive_access().filters.install(filter) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// `-1` as an id argument of the `set*id` calls, leaving that id unchanged.
+const ID_UNCHANGED: u32 = u32::MAX;
+
+fn optional_id(id: u32) -> Option<u32> {
+    (id != ID_UNCHANGED).then_some(id)
+}
+
+/// Changes the uids or, with `gid` set, the gids of the caller with `f`.
+fn set_ids(gid: bool, f: impl FnOnce(&mut IdSet, bool) -> bool) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let privileged = inner.cred.is_privileged();
+    let ids = if gid {
+        &mut inner.cred.gid
+    } else {
+        &mut inner.cred.uid
+    };
+    if f(ids, privileged) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Writes the real, effective and saved uids or, with `gid` set, gids of the caller.
+fn get_ids(gid: bool, real: *mut u32, effective: *mut u32, saved: *mut u32) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let token = inner.get_user_token();
+    let ids = if gid { inner.cred.gid } else { inner.cred.uid };
+    drop(inner);
+    *translated_mut(token, real) = ids.real;
+    *translated_mut(token, effective) = ids.effective;
+    *translated_mut(token, saved) = ids.saved;
+    0
+}
+
+pub fn sys_getuid() -> isize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .cred
+        .uid
+        .real as isize
+}
+
+pub fn sys_geteuid() -> isize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .cred
+        .uid
+        .effective as isize
+}
+
+pub fn sys_getgid() -> isize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .cred
+        .gid
+        .real as isize
+}
+
+pub fn sys_getegid() -> isize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .cred
+        .gid
+        .effective as isize
+}
+
+pub fn sys_setuid(uid: u32) -> isize {
+    set_ids(false, |ids, privileged| ids.set(uid, privileged))
+}
+
+pub fn sys_setgid(gid: u32) -> isize {
+    set_ids(true, |ids, privileged| ids.set(gid, privileged))
+}
+
+pub fn sys_setreuid(ruid: u32, euid: u32) -> isize {
+    set_ids(false, |ids, privileged| {
+        ids.set_re(optional_id(ruid), optional_id(euid), privileged)
+    })
+}
+
+pub fn sys_setregid(rgid: u32, egid: u32) -> isize {
+    set_ids(true, |ids, privileged| {
+        ids.set_re(optional_id(rgid), optional_id(egid), privileged)
+    })
+}
+
+pub fn sys_setresuid(ruid: u32, euid: u32, suid: u32) -> isize {
+    set_ids(false, |ids, privileged| {
+        ids.set_res(
+            optional_id(ruid),
+            optional_id(euid),
+            optional_id(suid),
+            privileged,
+        )
+    })
+}
+
+pub fn sys_setresgid(rgid: u32, egid: u32, sgid: u32) -> isize {
+    set_ids(true, |ids, privileged| {
+        ids.set_res(
+            optional_id(rgid),
+            optional_id(egid),
+            optional_id(sgid),
+            privileged,
+        )
+    })
+}
+
+pub fn sys_getresuid(ruid: *mut u32, euid: *mut u32, suid: *mut u32) -> isize {
+    get_ids(false, ruid, euid, suid)
+}
+
+pub fn sys_getresgid(rgid: *mut u32, egid: *mut u32, sgid: *mut u32) -> isize {
+    get_ids(true, rgid, egid, sgid)
 }
@@ -0,0 +1,174 @@
+use alloc::sync::Arc;
+
+use crate::mm::{translated_mut, translated_ref, VirtAddr};
+use crate::task::{
+    current_task, current_user_token, ptrace_detach, ptrace_resume, send_signal, TaskControlBlock,
+    TraceState, NSIG, SIGKILL, SIGSTOP,
+};
+
+/// `ptrace` requests, Linux values.
+const PTRACE_TRACEME: usize = 0;
+const PTRACE_PEEKTEXT: usize = 1;
+const PTRACE_PEEKDATA: usize = 2;
+const PTRACE_POKETEXT: usize = 4;
+const PTRACE_POKEDATA: usize = 5;
+const PTRACE_CONT: usize = 7;
+const PTRACE_KILL: usize = 8;
+const PTRACE_GETREGS: usize = 12;
+const PTRACE_SETREGS: usize = 13;
+const PTRACE_ATTACH: usize = 16;
+const PTRACE_DETACH: usize = 17;
+const PTRACE_SYSCALL: usize = 24;
+
+/// Registers of a tracee laid out like Linux's `user_regs_struct`: `pc`, then `x1` to `x31`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UserRegs([usize; 32]);
+
+/// Child `pid` of the caller if the caller traces it.
+fn tracee(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    let child = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .children
+        .iter()
+        .find(|child| child.getpid() == pid)
+        .cloned()?;
+    let traced = child.inner_exclusive_access().ptrace.is_some();
+    traced.then_some(child)
+}
+
+/// Child `pid` of the caller if the caller traces it and it is stopped for it.
+fn stopped_tracee(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    let child = tracee(pid)?;
+    let stopped = child
+        .inner_exclusive_access()
+        .ptrace
+        .is_some_and(|trace| trace.stop.is_some());
+    stopped.then_some(child)
+}
+
+/// Has the caller be traced by its parent.
+fn trace_me() -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.parent.is_none() || inner.ptrace.is_some() {
+        return -1;
+    }
+    inner.ptrace = Some(TraceState::default());
+    0
+}
+
+/// Starts tracing the child `pid` and stops it with `SIGSTOP`.
+fn attach(pid: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let cred = inner.cred;
+    let Some(child) = inner
+        .children
+        .iter()
+        .find(|child| child.getpid() == pid)
+        .cloned()
+    else {
+        return -1;
+    };
+    drop(inner);
+
+    let mut child_inner = child.inner_exclusive_access();
+    if child_inner.is_zombie() || child_inner.ptrace.is_some() || !cred.can_trace(&child_inner.cred)
+    {
+        return -1;
+    }
+    child_inner.ptrace = Some(TraceState::default());
+    drop(child_inner);
+    send_signal(&child, SIGSTOP);
+    0
+}
+
+fn peek(pid: usize, addr: usize, data: *mut usize) -> isize {
+    let Some(child) = stopped_tracee(pid) else {
+        return -1;
+    };
+    let mm = child.inner_exclusive_access().mm.clone();
+    let word = mm
+        .lock()
+        .memory_set
+        .user_word(VirtAddr::from(addr))
+        .map(|word| *word);
+    let Some(word) = word else {
+        return -1;
+    };
+    *translated_mut(current_user_token(), data) = word;
+    0
+}
+
+fn poke(pid: usize, addr: usize, data: usize) -> isize {
+    let Some(child) = stopped_tracee(pid) else {
+        return -1;
+    };
+    let mm = child.inner_exclusive_access().mm.clone();
+    let mm = mm.lock();
+    let Some(word) = mm.memory_set.user_word(VirtAddr::from(addr)) else {
+        return -1;
+    };
+    // Breakpoints go into code, the tracee fetches instructions anew on its way to user mode.
+    *word = data;
+    0
+}
+
+fn get_regs(pid: usize, regs: *mut UserRegs) -> isize {
+    let Some(child) = stopped_tracee(pid) else {
+        return -1;
+    };
+    let cx = child.inner_exclusive_access().get_trap_cx();
+    let mut user_regs = UserRegs(cx.x);
+    user_regs.0[0] = cx.sepc;
+    *translated_mut(current_user_token(), regs) = user_regs;
+    0
+}
+
+fn set_regs(pid: usize, regs: *const UserRegs) -> isize {
+    let Some(child) = stopped_tracee(pid) else {
+        return -1;
+    };
+    let user_regs = *translated_ref(current_user_token(), regs);
+    let cx = child.inner_exclusive_access().get_trap_cx();
+    cx.x[1..].copy_from_slice(&user_regs.0[1..]);
+    cx.sepc = user_regs.0[0];
+    0
+}
+
+/// Lets a parent debug its children. `PTRACE_PEEK*` store the word read at `data`.
+pub fn sys_ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    match request {
+        PTRACE_TRACEME => trace_me(),
+        PTRACE_ATTACH => attach(pid),
+        PTRACE_PEEKTEXT | PTRACE_PEEKDATA => peek(pid, addr, data as _),
+        PTRACE_POKETEXT | PTRACE_POKEDATA => poke(pid, addr, data),
+        PTRACE_GETREGS => get_regs(pid, data as _),
+        PTRACE_SETREGS => set_regs(pid, data as _),
+        PTRACE_CONT | PTRACE_SYSCALL | PTRACE_DETACH if data >= NSIG => -1,
+        PTRACE_CONT | PTRACE_SYSCALL => match tracee(pid) {
+            Some(child) if ptrace_resume(&child, data, request == PTRACE_SYSCALL) => 0,
+            _ => -1,
+        },
+        PTRACE_DETACH => {
+            let Some(child) = tracee(pid) else {
+                return -1;
+            };
+            ptrace_detach(&child);
+            if data != 0 {
+                send_signal(&child, data);
+            }
+            0
+        }
+        PTRACE_KILL => {
+            let Some(child) = tracee(pid) else {
+                return -1;
+            };
+            send_signal(&child, SIGKILL);
+            0
+        }
+        _ => -1,
+    }
+}
@@ -0,0 +1,248 @@
+use alloc::sync::Arc;
+use core::sync::atomic::AtomicU32;
+
+use crate::mm::{translated_mut, translated_ref, VirtAddr};
+use crate::sync::{
+    futex_requeue, futex_wait, futex_wake, Condvar, FutexError, InversionStats, Mutex,
+    MutexBlocking, MutexSpin, Resource, Semaphore, SpinLock, SyncTable, EINTR,
+};
+use crate::task::{current_task, current_user_token};
+
+/// What taking a mutex or semaphore returns if the caller would deadlock.
+const DEADLOCK: isize = -0xDEAD;
+
+/// `futex` operations, Linux values. `FUTEX_PRIVATE_FLAG` is accepted and ignored.
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+const FUTEX_REQUEUE: usize = 3;
+const FUTEX_PRIVATE_FLAG: usize = 128;
+
+/// What `FUTEX_WAIT` returns when the futex did not hold the expected value or timed out.
+const EAGAIN: isize = -11;
+const ETIMEDOUT: isize = -110;
+
+/// Linux `struct timespec`.
+#[repr(C)]
+struct TimeSpec {
+    sec: usize,
+    nsec: usize,
+}
+
+fn current_sync_table() -> (usize, Arc<SpinLock<SyncTable>>) {
+    let task = current_task().unwrap();
+    let table = task.inner_exclusive_access().sync.clone();
+    (task.getpid(), table)
+}
+
+/// Creates a blocking or yielding mutex and returns its id.
+pub fn sys_mutex_create(blocking: bool) -> isize {
+    let mutex: Arc<dyn Mutex> = if blocking {
+        Arc::new(MutexBlocking::new())
+    } else {
+        Arc::new(MutexSpin::new())
+    };
+    let (_, table) = current_sync_table();
+    let mut table = table.lock();
+    let id = table.mutexes.len();
+    table.mutexes.push(mutex);
+    table.deadlock.add_resource(Resource::Mutex(id), 1);
+    id as isize
+}
+
+/// Takes the mutex `mutex_id` for the task `tid`, unless that deadlocks or a signal interrupts.
+fn lock_mutex(
+    tid: usize,
+    table: &SpinLock<SyncTable>,
+    mutex_id: usize,
+    mutex: &dyn Mutex,
+) -> isize {
+    if !table
+        .lock()
+        .deadlock
+        .request(tid, Resource::Mutex(mutex_id))
+    {
+        return DEADLOCK;
+    }
+    if mutex.lock().is_err() {
+        table.lock().deadlock.cancel(tid, Resource::Mutex(mutex_id));
+        return EINTR;
+    }
+    table
+        .lock()
+        .deadlock
+        .acquire(tid, Resource::Mutex(mutex_id));
+    0
+}
+
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    let (tid, table) = current_sync_table();
+    let Some(mutex) = table.lock().mutexes.get(mutex_id).cloned() else {
+        return -1;
+    };
+    lock_mutex(tid, &table, mutex_id, mutex.as_ref())
+}
+
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    let (tid, table) = current_sync_table();
+    let Some(mutex) = table.lock().mutexes.get(mutex_id).cloned() else {
+        return -1;
+    };
+    if !mutex.unlock() {
+        return -1;
+    }
+    table
+        .lock()
+        .deadlock
+        .release(tid, Resource::Mutex(mutex_id));
+    0
+}
+
+/// Stores at `stats` the priority inversions seen on the blocking mutex `mutex_id`.
+pub fn sys_mutex_stats(mutex_id: usize, stats: *mut InversionStats) -> isize {
+    let (_, table) = current_sync_table();
+    let mutex = table.lock().mutexes.get(mutex_id).cloned();
+    let Some(inversion_stats) = mutex.and_then(|mutex| mutex.inversion_stats()) else {
+        return -1;
+    };
+    *translated_mut(current_user_token(), stats) = inversion_stats;
+    0
+}
+
+/// Creates a semaphore with `res_count` resources and returns its id.
+pub fn sys_semaphore_create(res_count: usize) -> isize {
+    let (_, table) = current_sync_table();
+    let mut table = table.lock();
+    let id = table.semaphores.len();
+    table.semaphores.push(Arc::new(Semaphore::new(res_count)));
+    table
+        .deadlock
+        .add_resource(Resource::Semaphore(id), res_count);
+    id as isize
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    let (tid, table) = current_sync_table();
+    let Some(sem) = table.lock().semaphores.get(sem_id).cloned() else {
+        return -1;
+    };
+    sem.up();
+    table
+        .lock()
+        .deadlock
+        .release(tid, Resource::Semaphore(sem_id));
+    0
+}
+
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    let (tid, table) = current_sync_table();
+    let Some(sem) = table.lock().semaphores.get(sem_id).cloned() else {
+        return -1;
+    };
+    if !table
+        .lock()
+        .deadlock
+        .request(tid, Resource::Semaphore(sem_id))
+    {
+        return DEADLOCK;
+    }
+    if sem.down().is_err() {
+        table
+            .lock()
+            .deadlock
+            .cancel(tid, Resource::Semaphore(sem_id));
+        return EINTR;
+    }
+    table
+        .lock()
+        .deadlock
+        .acquire(tid, Resource::Semaphore(sem_id));
+    0
+}
+
+/// Creates a condition variable and returns its id.
+pub fn sys_condvar_create() -> isize {
+    let (_, table) = current_sync_table();
+    let mut table = table.lock();
+    table.condvars.push(Arc::new(Condvar::new()));
+    table.condvars.len() as isize - 1
+}
+
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    let (_, table) = current_sync_table();
+    let Some(condvar) = table.lock().condvars.get(condvar_id).cloned() else {
+        return -1;
+    };
+    condvar.signal();
+    0
+}
+
+/// Waits on the condition variable `condvar_id` with the mutex `mutex_id` held.
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    let (tid, table) = current_sync_table();
+    let locked = table.lock();
+    let (Some(condvar), Some(mutex)) = (
+        locked.condvars.get(condvar_id).cloned(),
+        locked.mutexes.get(mutex_id).cloned(),
+    ) else {
+        return -1;
+    };
+    drop(locked);
+    let released = || {
+        table
+            .lock()
+            .deadlock
+            .release(tid, Resource::Mutex(mutex_id))
+    };
+    match condvar.wait(mutex.as_ref(), released) {
+        Ok(true) => lock_mutex(tid, &table, mutex_id, mutex.as_ref()),
+        Ok(false) => -1,
+        Err(_) => EINTR,
+    }
+}
+
+/// Enables deadlock detection, or disables it with `enabled` 0.
+pub fn sys_enable_deadlock_detect(enabled: usize) -> isize {
+    if enabled > 1 {
+        return -1;
+    }
+    let (_, table) = current_sync_table();
+    table.lock().deadlock.enabled = enabled == 1;
+    0
+}
+
+/// Physical address of the futex word at `uaddr` of the running task, with the word.
+fn futex_word(uaddr: usize) -> Option<(usize, &'static AtomicU32)> {
+    let mm = current_task().unwrap().inner_exclusive_access().mm.clone();
+    let word = mm.lock().memory_set.user_u32(VirtAddr::from(uaddr));
+    word
+}
+
+/// Waits and wakes up on the 32-bit word at `uaddr`. `FUTEX_REQUEUE` takes the number of tasks to
+/// move to `uaddr2` in place of `timeout`.
+pub fn sys_futex(uaddr: usize, op: usize, val: usize, timeout: usize, uaddr2: usize) -> isize {
+    let Some((key, word)) = futex_word(uaddr) else {
+        return -1;
+    };
+    match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => {
+            let timeout_us = (timeout != 0).then(|| {
+                let timeout = translated_ref(current_user_token(), timeout as *const TimeSpec);
+                timeout.sec * 1_000_000 + timeout.nsec.div_ceil(1000)
+            });
+            match futex_wait(key, word, val as u32, timeout_us) {
+                Ok(()) => 0,
+                Err(FutexError::WouldBlock) => EAGAIN,
+                Err(FutexError::TimedOut) => ETIMEDOUT,
+                Err(FutexError::Interrupted) => EINTR,
+            }
+        }
+        FUTEX_WAKE => futex_wake(key, val) as isize,
+        FUTEX_REQUEUE => {
+            let Some((key2, _)) = futex_word(uaddr2) else {
+                return -1;
+            };
+            futex_requeue(key, val, key2, timeout) as isize
+        }
+        _ => -1,
+    }
+}
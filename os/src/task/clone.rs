@@ -0,0 +1,69 @@
+use bitflags::bitflags;
+
+/// Low byte of the `clone` flags, the signal sent to the parent when the child exits.
+pub const CSIGNAL: usize = 0xff;
+
+bitflags! {
+    /// What a child created by `clone` shares with its creator, Linux values.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct CloneFlags: usize {
+        /// Share the address space instead of copying it.
+        const VM = 0x100;
+        /// Share the working directory, there is none yet.
+        const FS = 0x200;
+        /// Share the fd table instead of copying it.
+        const FILES = 0x400;
+        /// Share the signal handlers, there are none yet.
+        const SIGHAND = 0x800;
+        /// Suspend the creator until the child called `exec` or exited.
+        const VFORK = 0x4000;
+        /// The child gets the parent of its creator as parent.
+        const PARENT = 0x8000;
+        /// Put the child into the thread group of its creator, not supported.
+        const THREAD = 0x10000;
+        /// Set `tp` of the child to the `tls` argument.
+        const SETTLS = 0x80000;
+        /// Store the pid of the child at `parent_tid` in the address space of the creator.
+        const PARENT_SETTID = 0x100000;
+        /// Store the pid of the child at `child_tid` in the address space of the child.
+        const CHILD_SETTID = 0x1000000;
+    }
+}
+
+impl CloneFlags {
+    /// Whether the flags are supported together, signal handlers are only shared with memory.
+    pub fn is_valid(self) -> bool {
+        (!self.contains(Self::SIGHAND) || self.contains(Self::VM)) && !self.contains(Self::THREAD)
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn clone_test() {
+    use log::info;
+
+    use super::task::UserSpace;
+    use crate::mm::{MapPermission, MemorySet};
+
+    assert!((CloneFlags::VM | CloneFlags::SIGHAND | CloneFlags::FILES).is_valid());
+    assert!(!CloneFlags::SIGHAND.is_valid());
+    assert!(!(CloneFlags::VM | CloneFlags::THREAD).is_valid());
+
+    // Without `CLONE_VM` the child gets a copy of every page.
+    let mut memory_set = MemorySet::new_bare();
+    let perm = MapPermission::R | MapPermission::W | MapPermission::U;
+    memory_set.insert_framed_area(0x1000.into(), 0x3000.into(), perm);
+    let parent = UserSpace::new(memory_set, 0x3000);
+    let byte = |space: &UserSpace| {
+        space
+            .memory_set
+            .user_byte(0x2345.into(), MapPermission::W)
+            .unwrap()
+    };
+    *byte(&parent.lock()) = 42;
+    let child = parent.lock().fork();
+    assert_eq!(*byte(&child), 42);
+    *byte(&parent.lock()) = 7;
+    assert_eq!(*byte(&child), 42);
+
+    info!("clone_test passed !");
+}
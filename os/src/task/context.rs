@@ -1,3 +1,4 @@
+use super::kthread::kernel_thread_entry;
 use crate::trap::trap_return;
 
 #[derive(Clone, Copy, Default)]
@@ -13,6 +14,11 @@ impl TaskContext {
         Self::default()
     }
 
+    /// Return address and frame pointer of the function that switched away.
+    pub fn backtrace_start(&self) -> (usize, usize) {
+        (self.ra, self.s[0])
+    }
+
     pub fn goto_trap_return(kstack_ptr: usize) -> Self {
         Self {
             ra: trap_return as usize,
@@ -20,4 +26,12 @@ impl TaskContext {
             s: [0; 12],
         }
     }
+
+    pub fn goto_kernel_thread_entry(kstack_ptr: usize) -> Self {
+        Self {
+            ra: kernel_thread_entry as usize,
+            sp: kstack_ptr,
+            s: [0; 12],
+        }
+    }
 }
@@ -0,0 +1,319 @@
+use alloc::sync::Weak;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use log::{info, warn};
+
+use super::processor::current_task;
+use super::rlimit::RLIMIT_CORE;
+use crate::config::PAGE_SIZE;
+use crate::mm::{MapArea, MapPermission};
+use crate::println;
+use crate::trap::TrapContext;
+
+const ET_CORE: u16 = 4;
+const EM_RISCV: u16 = 243;
+/// Compressed instructions and the double-float ABI, what user programs are built for.
+const EF_RISCV: u32 = 0x5;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+/// Size of Linux's `struct elf_prstatus` on riscv64, and offset of the registers in it.
+const PRSTATUS_SIZE: usize = 376;
+const PRSTATUS_REGS: usize = 112;
+/// Note header, the name "CORE" padded to 8 bytes and the `elf_prstatus`.
+const NOTE_SIZE: usize = 12 + 8 + PRSTATUS_SIZE;
+
+/// Bytes of the core file per line of the dump.
+const LINE_BYTES: usize = 32;
+
+/// What is known about the crashed task besides its memory.
+struct CrashInfo {
+    sig: usize,
+    pid: usize,
+    ppid: usize,
+    pgid: usize,
+    sid: usize,
+    utime: usize,
+    stime: usize,
+    cx: TrapContext,
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+/// Prints a file to the console as lines `core <pid> <hex>`.
+struct SerialDump {
+    pid: usize,
+    line: [u8; LINE_BYTES],
+    len: usize,
+}
+
+impl SerialDump {
+    fn new(pid: usize) -> Self {
+        Self {
+            pid,
+            line: [0; LINE_BYTES],
+            len: 0,
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.line[self.len] = byte;
+            self.len += 1;
+            if self.len == LINE_BYTES {
+                self.flush();
+            }
+        }
+    }
+
+    fn write_zeros(&mut self, len: usize) {
+        (0..len).for_each(|_| self.write(&[0]));
+    }
+
+    fn flush(&mut self) {
+        if self.len > 0 {
+            println!("core {} {}", self.pid, Hex(&self.line[..self.len]));
+            self.len = 0;
+        }
+    }
+}
+
+fn push_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u64(buf: &mut Vec<u8>, value: usize) {
+    buf.extend_from_slice(&(value as u64).to_le_bytes());
+}
+
+fn push_phdr(buf: &mut Vec<u8>, p_type: u32, flags: u32, offset: usize, vaddr: usize, size: usize) {
+    push_u32(buf, p_type);
+    push_u32(buf, flags);
+    push_u64(buf, offset);
+    push_u64(buf, vaddr);
+    push_u64(buf, 0);
+    push_u64(buf, size);
+    push_u64(buf, size);
+    push_u64(buf, if p_type == PT_LOAD { PAGE_SIZE } else { 4 });
+}
+
+fn segment_flags(perm: MapPermission) -> u32 {
+    [
+        (MapPermission::R, PF_R),
+        (MapPermission::W, PF_W),
+        (MapPermission::X, PF_X),
+    ]
+    .into_iter()
+    .filter(|&(perm_bit, _)| perm.contains(perm_bit))
+    .fold(0, |flags, (_, flag)| flags | flag)
+}
+
+fn push_timeval(buf: &mut Vec<u8>, us: usize) {
+    push_u64(buf, us / 1_000_000);
+    push_u64(buf, us % 1_000_000);
+}
+
+/// The `NT_PRSTATUS` note, laid out like Linux's `struct elf_prstatus` on riscv64.
+fn push_prstatus(buf: &mut Vec<u8>, info: &CrashInfo) {
+    push_u32(buf, 5);
+    push_u32(buf, PRSTATUS_SIZE as u32);
+    push_u32(buf, NT_PRSTATUS);
+    buf.extend_from_slice(b"CORE\0\0\0\0");
+
+    let start = buf.len();
+    // `si_signo`, `si_code`, `si_errno`, `pr_cursig` and padding.
+    push_u32(buf, info.sig as u32);
+    push_u32(buf, 0);
+    push_u32(buf, 0);
+    push_u16(buf, info.sig as u16);
+    push_u16(buf, 0);
+    // `pr_sigpend` and `pr_sighold`.
+    push_u64(buf, 0);
+    push_u64(buf, 0);
+    for id in [info.pid, info.ppid, info.pgid, info.sid] {
+        push_u32(buf, id as u32);
+    }
+    push_timeval(buf, info.utime);
+    push_timeval(buf, info.stime);
+    push_timeval(buf, 0);
+    push_timeval(buf, 0);
+    debug_assert_eq!(buf.len() - start, PRSTATUS_REGS);
+    // `pc`, then `x1` to `x31`.
+    push_u64(buf, info.cx.sepc);
+    info.cx.x[1..].iter().for_each(|&reg| push_u64(buf, reg));
+    // `pr_fpvalid` and padding.
+    push_u32(buf, 0);
+    push_u32(buf, 0);
+    debug_assert_eq!(buf.len() - start, PRSTATUS_SIZE);
+}
+
+/// ELF header, program headers and the note, up to where the first segment starts.
+fn core_headers(info: &CrashInfo, areas: &[&MapArea], data_start: usize) -> Vec<u8> {
+    let phnum = 1 + areas.len();
+    let mut buf = Vec::with_capacity(data_start);
+
+    buf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    buf.resize(16, 0);
+    push_u16(&mut buf, ET_CORE);
+    push_u16(&mut buf, EM_RISCV);
+    push_u32(&mut buf, 1);
+    push_u64(&mut buf, 0);
+    push_u64(&mut buf, EHDR_SIZE);
+    push_u64(&mut buf, 0);
+    push_u32(&mut buf, EF_RISCV);
+    push_u16(&mut buf, EHDR_SIZE as u16);
+    push_u16(&mut buf, PHDR_SIZE as u16);
+    push_u16(&mut buf, phnum as u16);
+    push_u16(&mut buf, 0);
+    push_u16(&mut buf, 0);
+    push_u16(&mut buf, 0);
+
+    let note_offset = EHDR_SIZE + phnum * PHDR_SIZE;
+    push_phdr(&mut buf, PT_NOTE, 0, note_offset, 0, NOTE_SIZE);
+    let mut offset = data_start;
+    for area in areas {
+        let start: usize = area.start_va().into();
+        let size = usize::from(area.end_va()) - start;
+        let flags = segment_flags(area.permission());
+        push_phdr(&mut buf, PT_LOAD, flags, offset, start, size);
+        offset += size;
+    }
+
+    push_prstatus(&mut buf, info);
+    buf.resize(data_start, 0);
+    buf
+}
+
+/// Dumps the running task, which crashed with `sig`, as an ELF core file to the console.
+pub fn dump_core(sig: usize) {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let info = CrashInfo {
+        sig,
+        pid: task.getpid(),
+        ppid: inner
+            .parent
+            .as_ref()
+            .and_then(Weak::upgrade)
+            .map_or(0, |parent| parent.getpid()),
+        pgid: inner.pgid,
+        sid: inner.sid,
+        utime: inner.usage.utime,
+        stime: inner.usage.stime,
+        cx: *inner.get_trap_cx(),
+    };
+    let limit = inner.rlimits.cur(RLIMIT_CORE);
+    let mm = inner.mm.clone();
+    drop(inner);
+    drop(task);
+
+    let locked = mm.lock();
+    let areas: Vec<&MapArea> = locked.memory_set.user_areas().collect();
+    let headers_size = EHDR_SIZE + (1 + areas.len()) * PHDR_SIZE + NOTE_SIZE;
+    let data_start = headers_size.div_ceil(PAGE_SIZE) * PAGE_SIZE;
+    let size = data_start
+        + areas
+            .iter()
+            .map(|area| usize::from(area.end_va()) - usize::from(area.start_va()))
+            .sum::<usize>();
+    if size > limit {
+        drop(locked);
+        warn!(
+            "[kernel] Core of pid {} has {} bytes, more than RLIMIT_CORE, not dumped.",
+            info.pid, size
+        );
+        return;
+    }
+    let headers = core_headers(&info, &areas, data_start);
+    let ranges: Vec<(usize, usize)> = areas
+        .iter()
+        .map(|area| (area.start_va().into(), area.end_va().into()))
+        .collect();
+    drop(locked);
+
+    info!(
+        "[kernel] Dumping core of pid {}, {} bytes on lines starting with \"core {} \".",
+        info.pid, size, info.pid
+    );
+    let mut dump = SerialDump::new(info.pid);
+    dump.write(&headers);
+    // Pages are printed without the address space locked, unmapped ones come out as zeros.
+    let mut page = vec![0; PAGE_SIZE];
+    for (start, end) in ranges {
+        for va in (start..end).step_by(PAGE_SIZE) {
+            let mm = mm.lock();
+            match mm.memory_set.user_page(va.into()) {
+                Some(data) => {
+                    page.copy_from_slice(data);
+                    drop(mm);
+                    dump.write(&page);
+                }
+                None => {
+                    drop(mm);
+                    dump.write_zeros(PAGE_SIZE);
+                }
+            }
+        }
+    }
+    dump.flush();
+    info!("[kernel] Core of pid {} dumped.", info.pid);
+}
+
+#[cfg(feature = "selftest")]
+pub fn coredump_test() {
+    let read_u16 = |buf: &[u8], at: usize| u16::from_le_bytes(buf[at..at + 2].try_into().unwrap());
+    let read_u64 = |buf: &[u8], at: usize| u64::from_le_bytes(buf[at..at + 8].try_into().unwrap());
+
+    let info = CrashInfo {
+        sig: 11,
+        pid: 2,
+        ppid: 1,
+        pgid: 2,
+        sid: 1,
+        utime: 1_500_000,
+        stime: 0,
+        cx: TrapContext::app_init_context(0x1000, 0x2000, 0, 0, 0),
+    };
+    let headers = core_headers(&info, &[], PAGE_SIZE);
+    assert_eq!(headers.len(), PAGE_SIZE);
+    assert_eq!(&headers[..4], b"\x7fELF");
+    assert_eq!(read_u16(&headers, 16), ET_CORE);
+    assert_eq!(read_u16(&headers, 18), EM_RISCV);
+    assert_eq!(read_u16(&headers, 56), 1);
+
+    // The only program header is the note, right after it.
+    let note = EHDR_SIZE + PHDR_SIZE;
+    assert_eq!(read_u64(&headers, EHDR_SIZE + 8), note as u64);
+    assert_eq!(read_u64(&headers, EHDR_SIZE + 32), NOTE_SIZE as u64);
+    assert_eq!(&headers[note + 12..note + 16], b"CORE");
+    let prstatus = note + 20;
+    assert_eq!(headers[prstatus], 11);
+    assert_eq!(read_u64(&headers, prstatus + 48), 1);
+    assert_eq!(read_u64(&headers, prstatus + 56), 500_000);
+    assert_eq!(read_u64(&headers, prstatus + PRSTATUS_REGS), 0x1000);
+    assert_eq!(read_u64(&headers, prstatus + PRSTATUS_REGS + 16), 0x2000);
+
+    assert_eq!(
+        segment_flags(MapPermission::R | MapPermission::X),
+        PF_R | PF_X
+    );
+    info!("coredump_test passed !");
+}
@@ -0,0 +1,175 @@
+/// User id of the superuser, who passes every permission check.
+pub const ROOT_UID: u32 = 0;
+
+/// Real, effective and saved user or group id of a task.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct IdSet {
+    pub real: u32,
+    pub effective: u32,
+    pub saved: u32,
+}
+
+impl IdSet {
+    const fn new(id: u32) -> Self {
+        Self {
+            real: id,
+            effective: id,
+            saved: id,
+        }
+    }
+
+    fn contains(&self, id: u32) -> bool {
+        id == self.real || id == self.effective || id == self.saved
+    }
+
+    /// `setuid`: unprivileged tasks only switch the effective id.
+    pub fn set(&mut self, id: u32, privileged: bool) -> bool {
+        if privileged {
+            *self = Self::new(id);
+        } else if id == self.real || id == self.saved {
+            self.effective = id;
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// `setreuid`, `None` keeping an id.
+    pub fn set_re(&mut self, real: Option<u32>, effective: Option<u32>, privileged: bool) -> bool {
+        if !privileged
+            && (real.is_some_and(|id| id != self.real && id != self.effective)
+                || effective.is_some_and(|id| !self.contains(id)))
+        {
+            return false;
+        }
+        let old_real = self.real;
+        if let Some(id) = real {
+            self.real = id;
+        }
+        if let Some(id) = effective {
+            self.effective = id;
+        }
+        if real.is_some() || effective.is_some_and(|id| id != old_real) {
+            self.saved = self.effective;
+        }
+        true
+    }
+
+    /// `setresuid`, `None` keeping an id.
+    pub fn set_res(
+        &mut self,
+        real: Option<u32>,
+        effective: Option<u32>,
+        saved: Option<u32>,
+        privileged: bool,
+    ) -> bool {
+        if !privileged
+            && [real, effective, saved]
+                .into_iter()
+                .flatten()
+                .any(|id| !self.contains(id))
+        {
+            return false;
+        }
+        if let Some(id) = real {
+            self.real = id;
+        }
+        if let Some(id) = effective {
+            self.effective = id;
+        }
+        if let Some(id) = saved {
+            self.saved = id;
+        }
+        true
+    }
+}
+
+/// Identity a task acts with, inherited by its children and kept across `exec`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Credentials {
+    pub uid: IdSet,
+    pub gid: IdSet,
+}
+
+impl Credentials {
+    /// Those of the initproc and kernel threads.
+    pub const fn root() -> Self {
+        Self {
+            uid: IdSet::new(ROOT_UID),
+            gid: IdSet::new(ROOT_UID),
+        }
+    }
+
+    pub fn is_privileged(&self) -> bool {
+        self.uid.effective == ROOT_UID
+    }
+
+    /// Whether a task with these credentials may send signals to one with `target`'s.
+    pub fn can_signal(&self, target: &Credentials) -> bool {
+        self.is_privileged()
+            || [self.uid.real, self.uid.effective]
+                .into_iter()
+                .any(|id| id == target.uid.real || id == target.uid.saved)
+    }
+
+    /// Whether a task with these credentials may change the scheduling of one with `target`'s.
+    pub fn can_manage(&self, target: &Credentials) -> bool {
+        self.is_privileged()
+            || self.uid.effective == target.uid.real
+            || self.uid.effective == target.uid.effective
+    }
+
+    /// Whether a task with these credentials may trace one with `target`'s.
+    pub fn can_trace(&self, target: &Credentials) -> bool {
+        self.is_privileged()
+            || (target.uid == IdSet::new(self.uid.effective)
+                && target.gid == IdSet::new(self.gid.effective))
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn cred_test() {
+    use log::info;
+    let ids = |real, effective, saved| IdSet {
+        real,
+        effective,
+        saved,
+    };
+
+    // An unprivileged setuid only switches the effective id.
+    let mut uid = ids(1, 2, 3);
+    assert!(uid.set(3, false));
+    assert!(uid == ids(1, 3, 3));
+    assert!(!uid.set(4, false));
+    assert!(uid.set(4, true));
+    assert!(uid == IdSet::new(4));
+
+    // The saved id follows the effective one once it leaves the real id.
+    let mut uid = ids(1, 2, 3);
+    assert!(!uid.set_re(Some(3), None, false));
+    assert!(uid.set_re(None, Some(1), false));
+    assert!(uid == ids(1, 1, 3));
+    assert!(uid.set_re(None, Some(3), false));
+    assert!(uid == ids(1, 3, 3));
+    assert!(uid.set_re(Some(3), None, false));
+    assert!(uid == ids(3, 3, 3));
+
+    let mut uid = ids(1, 2, 3);
+    assert!(!uid.set_res(None, Some(4), None, false));
+    assert!(uid.set_res(Some(3), None, Some(1), false));
+    assert!(uid == ids(3, 2, 1));
+
+    let root = Credentials::root();
+    let user = |real, effective, saved| Credentials {
+        uid: ids(real, effective, saved),
+        gid: IdSet::new(real),
+    };
+    assert!(root.can_signal(&user(1, 1, 1)) && root.can_manage(&user(1, 1, 1)));
+    assert!(!user(1, 1, 1).can_signal(&root));
+    assert!(user(1, 2, 2).can_signal(&user(3, 3, 1)));
+    assert!(!user(1, 2, 2).can_manage(&user(3, 3, 1)));
+    assert!(user(1, 2, 2).can_manage(&user(4, 2, 4)));
+    assert!(!user(1, 2, 2).can_signal(&user(4, 2, 4)));
+
+    info!("cred_test passed !");
+}
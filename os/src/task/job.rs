@@ -0,0 +1,58 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::manager::all_tasks;
+use super::signal::{send_signal, SIGCONT, SIGHUP};
+use super::task::TaskControlBlock;
+
+/// Live tasks in the process group `pgid`.
+pub fn tasks_in_pgrp(pgid: usize) -> Vec<Arc<TaskControlBlock>> {
+    all_tasks()
+        .into_iter()
+        .filter(|task| task.inner_exclusive_access().pgid == pgid)
+        .collect()
+}
+
+/// Whether the process group `pgid` has a member in the session `sid`.
+pub fn pgrp_in_session(pgid: usize, sid: usize) -> bool {
+    tasks_in_pgrp(pgid)
+        .iter()
+        .any(|task| task.inner_exclusive_access().sid == sid)
+}
+
+/// Sends `sig` to every member of the process group `pgid`, returns whether there was one.
+pub fn kill_pgrp(pgid: usize, sig: usize) -> bool {
+    let tasks = tasks_in_pgrp(pgid);
+    for task in tasks.iter() {
+        send_signal(task, sig);
+    }
+    !tasks.is_empty()
+}
+
+/// Whether no member has a parent in another group of the same session to continue it.
+fn is_orphaned_pgrp(pgid: usize) -> bool {
+    tasks_in_pgrp(pgid).iter().all(|task| {
+        let inner = task.inner_exclusive_access();
+        let sid = inner.sid;
+        let parent = inner.parent.as_ref().and_then(|parent| parent.upgrade());
+        drop(inner);
+
+        parent.is_none_or(|parent| {
+            let parent_inner = parent.inner_exclusive_access();
+            parent_inner.pgid == pgid || parent_inner.sid != sid
+        })
+    })
+}
+
+/// Hangs up and continues the groups among `pgids` an exit orphaned with stopped members.
+pub fn handle_orphaned_pgrps(pgids: &[usize]) {
+    for &pgid in pgids {
+        let has_stopped = tasks_in_pgrp(pgid)
+            .iter()
+            .any(|task| task.inner_exclusive_access().stop_signal.is_some());
+        if has_stopped && is_orphaned_pgrp(pgid) {
+            kill_pgrp(pgid, SIGHUP);
+            kill_pgrp(pgid, SIGCONT);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+
+use super::exit_current_and_run_next;
+use super::manager::add_task;
+use super::processor::current_task;
+use super::task::TaskControlBlock;
+use crate::sync::enable_interrupts;
+
+/// Code run by a kernel thread.
+pub type KernelThreadEntry = Box<dyn FnOnce() + Send>;
+
+/// What a kernel thread returns to from its first `__switch`, instead of `trap_return`.
+pub fn kernel_thread_entry() -> ! {
+    enable_interrupts();
+    let entry = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .kthread_entry
+        .take()
+        .unwrap();
+    entry();
+    exit_current_and_run_next(0);
+    unreachable!("kernel_thread_entry");
+}
+
+/// Starts a kernel thread running `f`. Nobody waits for it, it is freed once it returned.
+pub fn spawn_kernel_thread<F>(f: F) -> Arc<TaskControlBlock>
+where
+    F: FnOnce() + Send + 'static,
+{
+    let task = Arc::new(TaskControlBlock::new_kernel_thread(Box::new(f)));
+    add_task(task.clone());
+    task
+}
+
+/// A kernel thread that is never queued, for self-tests that need a task.
+#[cfg(feature = "selftest")]
+pub fn test_task() -> Arc<TaskControlBlock> {
+    Arc::new(TaskControlBlock::new_kernel_thread(Box::new(|| {})))
+}
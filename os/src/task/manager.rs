@@ -1,60 +1,267 @@
-use alloc::collections::VecDeque;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 use lazy_static::lazy_static;
 
+use super::sched::{new_scheduler, EdfQueue, Scheduler};
 use super::task::TaskControlBlock;
-use crate::sync::UPSafeCell;
+use crate::config::MAX_HARTS;
+use crate::smp::{hart_id, kick_tickless_harts, online_harts};
+use crate::sync::{SpinLock, SpinLockGuard};
+use crate::timer::get_time_us;
 
-#[derive(Default)]
+/// Timer ticks between pulls of a task from the busiest hart.
+const BALANCE_INTERVAL_TICKS: usize = 10;
+
+/// Ready tasks of one hart, real-time ones run first.
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    rt: EdfQueue,
+    scheduler: Box<dyn Scheduler>,
+    ticks: usize,
 }
 
 impl TaskManager {
     pub fn new() -> Self {
-        Self::default()
+        Self {
+            rt: EdfQueue::new(),
+            scheduler: new_scheduler(),
+            ticks: 0,
+        }
     }
 
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+        if task.inner_exclusive_access().sched.rt.is_some() {
+            self.rt.add(task);
+        } else {
+            self.scheduler.add(task);
+        }
     }
 
-    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        // self.ready_queue.pop_front()
-        if self.ready_queue.is_empty() {
-            return None;
+    pub fn wakeup(&mut self, task: Arc<TaskControlBlock>) {
+        if task.inner_exclusive_access().sched.rt.is_some() {
+            self.rt.add(task);
+        } else {
+            self.scheduler.wakeup(task);
         }
-        let mut min_stride = self
-            .ready_queue
-            .get(0)
-            .unwrap()
-            .inner_exclusive_access()
-            .stride;
-
-        let mut index = 0;
-
-        for (i, task) in self.ready_queue.iter().enumerate() {
-            let inner = task.inner_exclusive_access();
-            if inner.stride < min_stride {
-                min_stride = inner.stride;
-                index = i;
-            }
+    }
+
+    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.rt
+            .fetch(get_time_us())
+            .or_else(|| self.scheduler.fetch())
+    }
+
+    pub fn tick(&mut self, current: &Arc<TaskControlBlock>) -> bool {
+        let now = get_time_us();
+        if current.inner_exclusive_access().sched.rt.is_some() {
+            return self.rt.tick(current, now);
         }
+        let expired = self.scheduler.tick(current);
+        expired || self.rt.earliest_deadline(now).is_some()
+    }
+
+    /// Whether any task is ready to run, after releasing the real-time jobs whose period began.
+    pub fn has_ready(&mut self, now: usize) -> bool {
+        self.rt.earliest_deadline(now).is_some()
+            || self.scheduler.queue_lens().iter().any(|&len| len > 0)
+    }
+
+    pub fn next_rt_release(&self) -> Option<usize> {
+        self.rt.next_release()
+    }
 
-        self.ready_queue.remove(index)
+    /// Queued tasks, throttled real-time ones included.
+    pub fn len(&self) -> usize {
+        self.rt.len() + self.scheduler.queue_lens().iter().sum::<usize>()
+    }
+
+    /// Only normal tasks migrate, real-time tasks stay where admission placed them.
+    pub fn steal(&mut self, hart: usize) -> Option<Arc<TaskControlBlock>> {
+        self.scheduler.steal(hart)
+    }
+
+    pub fn migrate(&mut self, task: Arc<TaskControlBlock>) {
+        self.scheduler.migrate(task);
+    }
+
+    pub fn queue_lens(&self) -> Vec<usize> {
+        self.scheduler.queue_lens()
     }
 }
 
 lazy_static! {
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
+    /// Run queue of every hart.
+    pub static ref TASK_MANAGERS: [SpinLock<TaskManager>; MAX_HARTS] =
+        core::array::from_fn(|_| SpinLock::new(TaskManager::new()));
+    static ref PID2TASK: SpinLock<BTreeMap<usize, Arc<TaskControlBlock>>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+/// Run queue of the running hart.
+pub fn local_manager() -> SpinLockGuard<'static, TaskManager> {
+    TASK_MANAGERS[hart_id()].lock()
+}
+
+/// The hart a task last ran on if it still may, otherwise the least loaded one it may run on.
+/// Real-time tasks always go to the hart their bandwidth is reserved on.
+fn select_hart(task: &Arc<TaskControlBlock>) -> usize {
+    let inner = task.inner_exclusive_access();
+    if let Some(rt) = inner.sched.rt {
+        return rt.hart;
+    }
+    let allowed = inner.affinity & online_harts();
+    let last = inner.sched.hart;
+    let is_new = inner.sched.is_new();
+    drop(inner);
+
+    if !is_new && allowed & (1 << last) != 0 {
+        return last;
+    }
+    (0..MAX_HARTS)
+        .filter(|hart| allowed & (1 << hart) != 0)
+        .min_by_key(|&hart| TASK_MANAGERS[hart].lock().len())
+        .unwrap_or_else(hart_id)
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
-    TASK_MANAGER.exclusive_access().add(task);
+    let hart = select_hart(&task);
+    TASK_MANAGERS[hart].lock().add(task);
+    kick_tickless_harts();
+}
+
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let hart = select_hart(&task);
+    TASK_MANAGERS[hart].lock().wakeup(task);
+    kick_tickless_harts();
+}
+
+/// Moves a task from the busiest other hart to `hart` if that one has `min_imbalance` tasks more,
+/// returns whether it did. Never holds two run queues at once.
+fn pull_task(hart: usize, min_imbalance: usize) -> bool {
+    let own_len = TASK_MANAGERS[hart].lock().len();
+    let busiest = (0..MAX_HARTS)
+        .filter(|&other| other != hart)
+        .map(|other| (other, TASK_MANAGERS[other].lock().len()))
+        .max_by_key(|&(_, len)| len);
+    let Some((busiest, len)) = busiest else {
+        return false;
+    };
+    if len < own_len + min_imbalance {
+        return false;
+    }
+    let Some(task) = TASK_MANAGERS[busiest].lock().steal(hart) else {
+        return false;
+    };
+    TASK_MANAGERS[hart].lock().migrate(task);
+    true
 }
 
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
-    TASK_MANAGER.exclusive_access().fetch()
+    let hart = hart_id();
+    if let Some(task) = TASK_MANAGERS[hart].lock().fetch() {
+        return Some(task);
+    }
+    // Nothing queued here, take work over from another hart.
+    if pull_task(hart, 1) {
+        return TASK_MANAGERS[hart].lock().fetch();
+    }
+    None
+}
+
+/// Accounts a timer tick and balances the run queues now and then, returns whether to preempt.
+pub fn tick_task(current: &Arc<TaskControlBlock>) -> bool {
+    let hart = hart_id();
+    let mut manager = TASK_MANAGERS[hart].lock();
+    let preempt = manager.tick(current);
+    manager.ticks += 1;
+    let balance = manager.ticks.is_multiple_of(BALANCE_INTERVAL_TICKS);
+    drop(manager);
+
+    if balance {
+        pull_task(hart, 2);
+    }
+    preempt || !current.can_run_on(hart)
+}
+
+/// Number of ready tasks in each queue of the scheduler, summed over all harts.
+pub fn sched_queue_lens() -> Vec<usize> {
+    let mut lens = Vec::new();
+    for manager in TASK_MANAGERS.iter() {
+        for (i, len) in manager.lock().queue_lens().into_iter().enumerate() {
+            if i == lens.len() {
+                lens.push(0);
+            }
+            lens[i] += len;
+        }
+    }
+    lens
+}
+
+pub fn insert_into_pid2task(pid: usize, task: Arc<TaskControlBlock>) {
+    PID2TASK.lock().insert(pid, task);
+}
+
+pub fn remove_from_pid2task(pid: usize) {
+    PID2TASK.lock().remove(&pid);
+}
+
+/// All live tasks.
+pub fn all_tasks() -> Vec<Arc<TaskControlBlock>> {
+    PID2TASK.lock().values().cloned().collect()
+}
+
+/// Live task with the given pid, exited tasks are no longer found.
+pub fn pid2task(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    PID2TASK.lock().get(&pid).cloned()
+}
+
+#[cfg(feature = "selftest")]
+pub fn affinity_test() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use log::info;
+
+    use super::kthread::test_task;
+    use super::{current_task, suspend_current_and_run_next};
+    use crate::selftest::wait_until;
+
+    // Another hart only takes over the tasks allowed to run on it.
+    let mut manager = TaskManager::new();
+    let (pinned, free) = (test_task(), test_task());
+    pinned.inner_exclusive_access().affinity = 1;
+    manager.add(pinned.clone());
+    manager.add(free.clone());
+    let other = MAX_HARTS - 1;
+    assert!(Arc::ptr_eq(&manager.steal(other).unwrap(), &free));
+    assert!(manager.steal(other).is_none());
+    assert!(Arc::ptr_eq(&manager.steal(0).unwrap(), &pinned));
+
+    // A running thread moves over once its affinity excludes the hart it is on.
+    let online = online_harts();
+    let first = online.trailing_zeros() as usize;
+    let second = (usize::BITS - 1 - online.leading_zeros()) as usize;
+    let seen = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+    let done = Arc::new(AtomicUsize::new(0));
+
+    let (thread_seen, thread_done) = (seen.clone(), done.clone());
+    let task = Arc::new(TaskControlBlock::new_kernel_thread(Box::new(move || {
+        for (phase, hart) in [first, second].into_iter().enumerate() {
+            current_task().unwrap().inner_exclusive_access().affinity = 1 << hart;
+            suspend_current_and_run_next();
+            for _ in 0..20 {
+                thread_seen[phase].fetch_or(1 << hart_id(), Ordering::SeqCst);
+                suspend_current_and_run_next();
+            }
+        }
+        thread_done.store(1, Ordering::SeqCst);
+    })));
+    task.inner_exclusive_access().affinity = 1 << first;
+    add_task(task);
+    wait_until(|| done.load(Ordering::SeqCst) == 1);
+    assert_eq!(seen[0].load(Ordering::SeqCst), 1 << first);
+    assert_eq!(seen[1].load(Ordering::SeqCst), 1 << second);
+
+    info!("affinity_test passed !");
 }
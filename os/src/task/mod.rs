@@ -1,42 +1,252 @@
+mod clone;
 mod context;
+mod coredump;
+mod cred;
+mod job;
+mod kthread;
 mod manager;
 mod pid;
 mod processor;
+mod ptrace;
+mod rlimit;
+mod sched;
+mod seccomp;
+mod signal;
 mod switch;
 mod task;
+mod usage;
+mod watchdog;
+mod workqueue;
 
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
+#[cfg(feature = "selftest")]
+pub use clone::clone_test;
+pub use clone::{CloneFlags, CSIGNAL};
 pub use context::TaskContext;
+#[cfg(feature = "selftest")]
+pub use coredump::coredump_test;
+pub use coredump::dump_core;
+#[cfg(feature = "selftest")]
+pub use cred::cred_test;
+pub use cred::IdSet;
+use job::handle_orphaned_pgrps;
+pub use job::{kill_pgrp, pgrp_in_session, tasks_in_pgrp};
+#[allow(unused)]
+pub use kthread::spawn_kernel_thread;
 use lazy_static::lazy_static;
 use log::info;
-pub use manager::add_task;
+#[cfg(feature = "selftest")]
+pub use manager::affinity_test;
+pub use manager::{
+    add_task, all_tasks, insert_into_pid2task, pid2task, remove_from_pid2task, sched_queue_lens,
+    wakeup_task,
+};
+use manager::{local_manager, tick_task};
 use processor::schedule;
 pub use processor::{
-    current_task, current_trap_cx, current_user_token, run_tasks, take_current_task,
+    current_task, current_trap_cx, current_trap_cx_user_va, current_user_token, run_tasks,
+    take_current_task,
+};
+#[cfg(feature = "selftest")]
+pub use ptrace::ptrace_test;
+pub use ptrace::{ptrace_detach, ptrace_exec_trap, ptrace_resume, ptrace_syscall_stop, TraceState};
+#[cfg(feature = "selftest")]
+pub use rlimit::rlimit_test;
+use rlimit::RLIMIT_CPU;
+pub use rlimit::{RLimit, RLIMIT_NOFILE, RLIMIT_NPROC};
+#[cfg(feature = "selftest")]
+pub use seccomp::seccomp_test;
+pub use seccomp::{FilterAction, FilterRule, SyscallFilter};
+#[cfg(feature = "selftest")]
+pub use signal::signal_test;
+pub use signal::{
+    current_has_pending_signal, handle_signals, send_signal, NSIG, SIGBUS, SIGCONT, SIGILL, SIGINT,
+    SIGKILL, SIGPIPE, SIGQUIT, SIGSEGV, SIGSTOP, SIGSYS, SIGTRAP, SIGTSTP, SIGXCPU,
 };
-pub use task::{TaskInfo, TaskStatus};
+pub use task::{TaskControlBlock, TaskInfo, TaskStatus};
+#[cfg(feature = "selftest")]
+pub use usage::usage_test;
+pub use usage::ResourceUsage;
+#[cfg(feature = "selftest")]
+pub use watchdog::watchdog_test;
+pub use watchdog::{touch_watchdog, watchdog_tick};
+#[cfg(feature = "selftest")]
+pub use workqueue::workqueue_test;
+pub use workqueue::{init_workqueue, schedule_work};
 
-use self::task::TaskControlBlock;
+use self::sched::{admit_rt, release_rt, RtParams};
+#[cfg(feature = "selftest")]
+pub use self::sched::{cfs_test, edf_test, mlfq_test};
+use crate::fs::FdTable;
 use crate::loader::get_app_data_by_name;
+use crate::mm::VirtAddr;
+use crate::smp::{hart_id, set_tickless};
+use crate::sync::{disable_interrupts, release_held_mutexes, restore_interrupts, SpinLock};
+use crate::timer::{get_time_us, next_timer_expiry, set_next_trigger, set_trigger_at_us};
+use crate::trap::save_fp_state;
 
-pub fn suspend_current_and_run_next() {
+/// Takes the running task off the hart in the given state, charging it the time it ran and the
+/// context switch.
+fn take_current_for_switch(
+    status: TaskStatus,
+    voluntary: bool,
+) -> (Arc<TaskControlBlock>, *mut TaskContext) {
     let task = take_current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
 
-    task_inner.task_status = TaskStatus::Ready;
+    let now = get_time_us();
+    task_inner.task_status = status;
+    task_inner.sched.update_curr(now);
+    task_inner.usage.switch_out(now, voluntary);
+    task_inner.last_scheduled = now;
+    // The next task may use the floating-point registers, kernel threads have none of their own.
+    if !task_inner.is_kthread {
+        save_fp_state(task_inner.get_trap_cx());
+    }
 
     drop(task_inner);
+    (task, task_cx_ptr)
+}
 
+/// Gives up the hart voluntarily, the task stays ready.
+pub fn suspend_current_and_run_next() {
+    let irq = disable_interrupts();
+    let (task, task_cx_ptr) = take_current_for_switch(TaskStatus::Ready, true);
     add_task(task);
+    schedule(task_cx_ptr);
+    restore_interrupts(irq);
+}
+
+/// Takes the hart away from the running task, which stays ready.
+pub fn preempt_current_and_run_next() {
+    let irq = disable_interrupts();
+    let (task, task_cx_ptr) = take_current_for_switch(TaskStatus::Ready, false);
+    add_task(task);
+    schedule(task_cx_ptr);
+    restore_interrupts(irq);
+}
 
+/// Takes the running task off the hart until `wakeup_task`. The caller keeps preemption disabled
+/// from making itself known to its waker until here.
+pub fn block_current_and_run_next() {
+    let irq = disable_interrupts();
+    let (_, task_cx_ptr) = take_current_for_switch(TaskStatus::Blocked, true);
     schedule(task_cx_ptr);
+    restore_interrupts(irq);
+}
+
+/// Stops the running task by `sig` until a `SIGCONT` or `SIGKILL` wakes it up, unless one of them
+/// arrived since the task took the signal.
+pub fn stop_current_and_run_next(sig: usize) {
+    let irq = disable_interrupts();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.signals.contains(SIGCONT) || inner.signals.contains(SIGKILL) {
+        drop(inner);
+        restore_interrupts(irq);
+        return;
+    }
+    // From here on a `SIGCONT` wakes the task up, even if it did not switch away yet.
+    inner.stop_signal = Some(sig);
+    inner.stop_reported = false;
+    drop(inner);
+    drop(task);
+
+    let (_, task_cx_ptr) = take_current_for_switch(TaskStatus::Stopped, true);
+    schedule(task_cx_ptr);
+    restore_interrupts(irq);
+}
+
+/// Longest a tickless hart goes without a timer interrupt.
+const TICKLESS_MAX_US: usize = 1_000_000;
+
+/// Programs the next timer interrupt of this hart: a tick while the running task may have to be
+/// preempted, otherwise the next event that makes a task ready. Returns whether it went tickless.
+pub fn arm_timer() -> bool {
+    let now = get_time_us();
+    let rt_running =
+        current_task().is_some_and(|task| task.inner_exclusive_access().sched.rt.is_some());
+
+    // Published before looking at the ready queues: a task queued concurrently is either seen
+    // below or followed by an IPI.
+    set_tickless(true);
+    let mut manager = local_manager();
+    if rt_running || manager.has_ready(now) {
+        drop(manager);
+        set_tickless(false);
+        set_next_trigger();
+        return false;
+    }
+    let next_release = manager.next_rt_release();
+    drop(manager);
+
+    let wake = [next_release, next_timer_expiry()]
+        .into_iter()
+        .flatten()
+        .fold(now + TICKLESS_MAX_US, usize::min);
+    set_trigger_at_us(wake);
+    true
+}
+
+/// Accounts a timer tick to the running task, returns whether the scheduling policy wants it to
+/// give up the hart.
+pub fn timer_tick() -> bool {
+    let task = current_task().unwrap();
+    tick_task(&task)
+}
+
+/// Whether the running task used more user and system time than its `RLIMIT_CPU`.
+pub fn current_exceeds_cpu_limit() -> bool {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let used_us = inner.usage.utime + inner.usage.stime;
+    used_us / 1_000_000 >= inner.rlimits.cur(RLIMIT_CPU)
+}
+
+/// Moves the running task into the real-time class with the given reservation in microseconds, or
+/// back to the normal class if `runtime` is 0. Returns false if the parameters are invalid or the
+/// reservation does not pass admission control.
+pub fn set_current_deadline(runtime: usize, period: usize, deadline: usize) -> bool {
+    let task = current_task().unwrap();
+    let old = task.inner_exclusive_access().sched.rt;
+
+    if runtime == 0 {
+        if let Some(old) = old {
+            release_rt(old.hart, old.bandwidth());
+        }
+        task.inner_exclusive_access().sched.rt = None;
+        return true;
+    }
+    if runtime > deadline || deadline > period {
+        return false;
+    }
+
+    // The task is admitted on the hart it runs on and stays there.
+    let hart = hart_id();
+    let rt = RtParams::new(runtime, period, deadline, get_time_us(), hart);
+    let old_here = old.filter(|old| old.hart == hart);
+    if !admit_rt(
+        hart,
+        old_here.map_or(0, |old| old.bandwidth()),
+        rt.bandwidth(),
+    ) {
+        return false;
+    }
+    if let Some(old) = old.filter(|old| old.hart != hart) {
+        release_rt(old.hart, old.bandwidth());
+    }
+    task.inner_exclusive_access().sched.rt = Some(rt);
+    true
 }
 
 pub const IDLE_PID: usize = 0;
 
 pub fn exit_current_and_run_next(exit_code: i32) {
+    // Without a current task there is nothing an interrupt could preempt.
+    disable_interrupts();
     let task = take_current_task().unwrap();
 
     let pid = task.getpid();
@@ -44,25 +254,69 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         info!("[kernel] Idle process exit with exit_code {}...", exit_code);
         panic!("All application completed!");
     }
+    remove_from_pid2task(pid);
+    release_held_mutexes(&task);
+    let sync = task.inner_exclusive_access().sync.clone();
+    sync.lock().deadlock.forget(pid);
 
     let mut inner = task.inner_exclusive_access();
 
     inner.task_status = TaskStatus::Zombie;
     inner.exit_code = exit_code;
+    inner.usage.charge_kernel(get_time_us());
+
+    let children = core::mem::take(&mut inner.children);
+    let mut pgids: Vec<usize> = children
+        .iter()
+        .map(|child| child.inner_exclusive_access().pgid)
+        .collect();
+    pgids.push(inner.pgid);
+    pgids.sort_unstable();
+    pgids.dedup();
+    let mut mm = inner.mm.lock();
+    if Arc::strong_count(&inner.mm) == 1 {
+        // Freeing the frames of a large address space takes a while, the worker does it later.
+        let areas = mm.memory_set.take_areas();
+        schedule_work(move || drop(areas));
+    } else {
+        // Still used by the tasks sharing it, only the trap context goes.
+        let trap_cx_vpn = VirtAddr::from(inner.trap_cx_va).into();
+        mm.memory_set.remove_area_with_start_vpn(trap_cx_vpn);
+    }
+    drop(mm);
+    let rt = inner.sched.rt.take();
+    let vfork_parent = inner.vfork_parent.take();
+    // Closing a pipe end wakes up tasks, which must not happen with the task locked.
+    let fd_table = core::mem::replace(
+        &mut inner.fd_table,
+        Arc::new(SpinLock::new(FdTable::default())),
+    );
+
+    drop(inner);
+    drop(task);
+    drop(fd_table);
 
+    // Parents are always locked before their children, so the task must not be locked while
+    // handing its children over to the initproc, which may be waiting for it on another hart.
     {
         let mut initproc_inner = INITPROC.inner_exclusive_access();
-        for child in inner.children.iter() {
+        for child in children {
+            // The initproc does not trace, a tracee goes on as if detached.
+            ptrace_detach(&child);
             child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-            initproc_inner.children.push(child.clone());
+            initproc_inner.children.push(child);
         }
     }
+    // With the task and its children gone, groups may have lost their last parent that could
+    // continue them.
+    handle_orphaned_pgrps(&pgids);
 
-    inner.children.clear();
-    inner.memory_set.recycle_data_pages();
-
-    drop(inner);
-    drop(task);
+    if let Some(rt) = rt {
+        release_rt(rt.hart, rt.bandwidth());
+    }
+    if let Some(parent) = vfork_parent {
+        wakeup_task(parent);
+    }
 
     let mut _unused = TaskContext::zero_init();
     schedule(&mut _unused as *mut _);
@@ -70,10 +324,12 @@ pub fn exit_current_and_run_next(exit_code: i32) {
 
 lazy_static! {
     pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new(TaskControlBlock::new(
+        "ch5b_initproc",
         get_app_data_by_name("ch5b_initproc").unwrap()
     ));
 }
 
 pub fn add_initproc() {
+    insert_into_pid2task(INITPROC.getpid(), INITPROC.clone());
     add_task(INITPROC.clone());
 }
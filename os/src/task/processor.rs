@@ -1,16 +1,23 @@
 use alloc::sync::Arc;
+use core::arch::asm;
+use core::cell::RefMut;
+use core::hint::spin_loop;
+use core::sync::atomic::Ordering;
 
 use lazy_static::lazy_static;
 
 use super::context::TaskContext;
-use super::manager::fetch_task;
+use super::manager::{add_task, fetch_task};
 use super::switch::__switch;
 use super::task::TaskControlBlock;
-use super::TaskStatus;
-use crate::config::BIG_STRIDE;
-use crate::sync::UPSafeCell;
-use crate::timer::get_time_us;
+use super::watchdog::{touch_watchdog, watchdog_switch, watchdog_tick};
+use super::{arm_timer, TaskStatus};
+use crate::config::MAX_HARTS;
+use crate::smp::{clear_ipi, hart_id};
+use crate::sync::{switch_in, switch_out, IrqGuard, UPSafeCell};
+use crate::timer::{check_timer, get_time_us};
 use crate::trap::TrapContext;
+use crate::tty::poll_console;
 
 pub struct Processor {
     current: Option<Arc<TaskControlBlock>>,
@@ -18,7 +25,13 @@ pub struct Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    /// One processor per hart, each only ever accessed by its own hart.
+    pub static ref PROCESSORS: [UPSafeCell<Processor>; MAX_HARTS] =
+        core::array::from_fn(|_| unsafe { UPSafeCell::new(Processor::new()) });
+}
+
+fn current_processor() -> RefMut<'static, Processor> {
+    PROCESSORS[hart_id()].exclusive_access()
 }
 
 impl Processor {
@@ -44,35 +57,79 @@ impl Processor {
 
 pub fn run_tasks() {
     loop {
-        let mut processor = PROCESSOR.exclusive_access();
+        touch_watchdog();
+        let mut processor = current_processor();
         if let Some(task) = fetch_task() {
+            // Its affinity changed while it was queued, move it to a hart it may run on.
+            if !task.can_run_on(hart_id()) {
+                drop(processor);
+                add_task(task);
+                continue;
+            }
+            // A task requeued by another hart may still be on its way out of `__switch`.
+            while task.on_cpu.load(Ordering::Acquire) {
+                spin_loop();
+            }
+            task.on_cpu.store(true, Ordering::Relaxed);
+
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             let mut task_inner = task.inner_exclusive_access();
             let next_task_ptr = &task_inner.task_cx as *const TaskContext;
             task_inner.task_status = TaskStatus::Running;
+            let now = get_time_us();
             if task_inner.start_time == 0 {
-                task_inner.start_time = get_time_us();
+                task_inner.start_time = now;
             }
-
-            task_inner.stride += BIG_STRIDE / task_inner.priority;
+            task_inner.sched.exec_start = now;
+            task_inner.sched.hart = hart_id();
+            task_inner.usage.switch_in(now);
+            task_inner.last_scheduled = now;
             drop(task_inner);
+            watchdog_switch(task.getpid());
 
-            processor.current = Some(task);
+            // The idle loop keeps its own reference until the task switched back, so it cannot
+            // be reaped while still running on its kernel stack.
+            processor.current = Some(task.clone());
             drop(processor);
 
             unsafe {
+                // Kernel stacks are mapped by whichever hart created the task, this hart may still
+                // cache a translation of a recycled stack slot.
+                asm!("sfence.vma");
                 __switch(idle_task_cx_ptr, next_task_ptr);
             }
+            task.on_cpu.store(false, Ordering::Release);
+            watchdog_switch(0);
+        } else {
+            drop(processor);
+            idle();
         }
     }
 }
 
+/// Waits for an interrupt while there is nothing to run. Interrupts stay masked in `sstatus`, `wfi`
+/// returns anyway and they are dealt with here.
+fn idle() {
+    if arm_timer() {
+        unsafe {
+            asm!("wfi");
+        }
+    }
+    clear_ipi();
+    check_timer();
+    poll_console();
+    watchdog_tick();
+}
+
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    let _irq = IrqGuard::new();
+    current_processor().take_current()
 }
 
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    // Neither moved to another hart nor interrupted while looking at the processor.
+    let _irq = IrqGuard::new();
+    current_processor().current()
 }
 
 pub fn current_user_token() -> usize {
@@ -81,6 +138,11 @@ pub fn current_user_token() -> usize {
     token
 }
 
+/// Address of the trap context of the running task in its address space.
+pub fn current_trap_cx_user_va() -> usize {
+    current_task().unwrap().inner_exclusive_access().trap_cx_va
+}
+
 pub fn current_trap_cx() -> &'static mut TrapContext {
     current_task()
         .unwrap()
@@ -88,12 +150,16 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
         .get_trap_cx()
 }
 
+/// Switches to the idle loop of this hart, must be called with interrupts disabled. The preempt
+/// count of the task goes along with it.
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
-    let mut processor = PROCESSOR.exclusive_access();
+    let preempt_count = switch_out();
+    let mut processor = current_processor();
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     drop(processor);
 
     unsafe {
         __switch(switched_task_cx_ptr, idle_task_cx_ptr);
     }
+    switch_in(preempt_count);
 }
@@ -0,0 +1,157 @@
+use alloc::sync::Arc;
+
+use super::manager::wakeup_task;
+use super::processor::{current_task, schedule};
+use super::signal::{send_signal, SIGKILL, SIGTRAP};
+use super::take_current_for_switch;
+use super::task::{TaskControlBlock, TaskControlBlockInner, TaskStatus};
+use crate::sync::{disable_interrupts, restore_interrupts};
+
+/// What a syscall stop is reported with, like Linux's `PTRACE_O_TRACESYSGOOD`.
+pub const SYSCALL_TRAP: usize = SIGTRAP | 0x80;
+
+/// A task traced by its parent.
+#[derive(Clone, Copy, Default)]
+pub struct TraceState {
+    /// Stop at the entry and exit of every syscall.
+    pub syscalls: bool,
+    /// Signal or `SYSCALL_TRAP` the task stopped for its tracer with, `None` while it runs.
+    pub stop: Option<usize>,
+    /// Whether `waitpid` reported the current stop to the tracer already.
+    pub stop_reported: bool,
+    /// Signal the tracer resumed the task with.
+    pub resume_signal: usize,
+}
+
+/// Stops the running task for its tracer with `why`, returns the signal it was resumed with.
+pub fn ptrace_stop(why: usize) -> Option<usize> {
+    let irq = disable_interrupts();
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let killed = inner.signals.contains(SIGKILL);
+    let Some(trace) = inner.ptrace.as_mut() else {
+        drop(inner);
+        restore_interrupts(irq);
+        return None;
+    };
+    if killed {
+        drop(inner);
+        restore_interrupts(irq);
+        return Some(0);
+    }
+    // From here on the tracer can resume the task, even if it did not switch away yet.
+    trace.stop = Some(why);
+    trace.stop_reported = false;
+    trace.resume_signal = 0;
+    drop(inner);
+    drop(task);
+
+    let (_, task_cx_ptr) = take_current_for_switch(TaskStatus::Traced, true);
+    schedule(task_cx_ptr);
+    restore_interrupts(irq);
+
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    Some(inner.ptrace.map_or(0, |trace| trace.resume_signal))
+}
+
+/// Stops the running task at the entry or exit of a syscall if its tracer asked for it.
+pub fn ptrace_syscall_stop() {
+    let traces_syscalls = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .ptrace
+        .is_some_and(|trace| trace.syscalls);
+    if traces_syscalls {
+        ptrace_stop(SYSCALL_TRAP);
+    }
+}
+
+/// Sends `SIGTRAP` to the running task after `exec` if it is traced.
+pub fn ptrace_exec_trap() {
+    let task = current_task().unwrap();
+    if task.inner_exclusive_access().ptrace.is_some() {
+        send_signal(&task, SIGTRAP);
+    }
+}
+
+/// Ends the stop of a task stopped for its tracer, returns whether it has to be woken up.
+pub fn end_trace_stop(inner: &mut TaskControlBlockInner) -> bool {
+    let stopped = inner
+        .ptrace
+        .as_mut()
+        .and_then(|trace| trace.stop.take())
+        .is_some();
+    if stopped {
+        inner.task_status = TaskStatus::Ready;
+    }
+    stopped
+}
+
+/// Resumes a task stopped for its tracer, returns false if it is not stopped.
+pub fn ptrace_resume(task: &Arc<TaskControlBlock>, sig: usize, syscalls: bool) -> bool {
+    let mut inner = task.inner_exclusive_access();
+    match inner.ptrace.as_mut() {
+        Some(trace) if trace.stop.is_some() => {
+            trace.syscalls = syscalls;
+            trace.resume_signal = sig;
+        }
+        _ => return false,
+    }
+    end_trace_stop(&mut inner);
+    drop(inner);
+    wakeup_task(task.clone());
+    true
+}
+
+/// Stops tracing a task, which resumes if it was stopped for its tracer.
+pub fn ptrace_detach(task: &Arc<TaskControlBlock>) {
+    let mut inner = task.inner_exclusive_access();
+    let wake = end_trace_stop(&mut inner);
+    inner.ptrace = None;
+    drop(inner);
+    if wake {
+        wakeup_task(task.clone());
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn ptrace_test() {
+    use alloc::boxed::Box;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use log::info;
+
+    use super::manager::add_task;
+    use super::signal::SIGCHLD;
+    use crate::selftest::wait_until;
+
+    assert!(ptrace_stop(SIGTRAP).is_none());
+
+    // A traced thread stops for a signal, then at a syscall once resumed with `syscalls` set.
+    let resumed = Arc::new(AtomicUsize::new(usize::MAX));
+    let done = Arc::new(AtomicUsize::new(0));
+    let (thread_resumed, thread_done) = (resumed.clone(), done.clone());
+    let task = Arc::new(TaskControlBlock::new_kernel_thread(Box::new(move || {
+        thread_resumed.store(ptrace_stop(SIGTRAP).unwrap(), Ordering::SeqCst);
+        ptrace_syscall_stop();
+        thread_done.store(1, Ordering::SeqCst);
+    })));
+    task.inner_exclusive_access().ptrace = Some(TraceState::default());
+    add_task(task.clone());
+
+    let wait_stop =
+        |why| wait_until(|| task.inner_exclusive_access().ptrace.unwrap().stop == Some(why));
+    wait_stop(SIGTRAP);
+    assert!(ptrace_resume(&task, SIGCHLD, true));
+    assert!(!ptrace_resume(&task, SIGCHLD, true));
+    wait_stop(SYSCALL_TRAP);
+    assert_eq!(resumed.load(Ordering::SeqCst), SIGCHLD);
+
+    // Detaching lets it go on untraced.
+    ptrace_detach(&task);
+    assert!(task.inner_exclusive_access().ptrace.is_none());
+    wait_until(|| done.load(Ordering::SeqCst) == 1);
+
+    info!("ptrace_test passed !");
+}
@@ -0,0 +1,88 @@
+/// Resource numbers of Linux.
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIM_NLIMITS: usize = 16;
+
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+/// Soft and hard limit of one resource, laid out like Linux's `struct rlimit`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+impl RLimit {
+    const INFINITY: Self = Self {
+        cur: RLIM_INFINITY,
+        max: RLIM_INFINITY,
+    };
+}
+
+/// Limits of a task, inherited by its children. `RLIMIT_CPU` is in seconds, sizes in bytes.
+#[derive(Clone, Copy)]
+pub struct ResourceLimits {
+    limits: [RLimit; RLIM_NLIMITS],
+}
+
+impl ResourceLimits {
+    pub fn new() -> Self {
+        let mut limits = [RLimit::INFINITY; RLIM_NLIMITS];
+        limits[RLIMIT_NOFILE] = RLimit {
+            cur: 1024,
+            max: 4096,
+        };
+        // Core dumps go to the console and take long, only tasks that ask for them get them.
+        limits[RLIMIT_CORE].cur = 0;
+        Self { limits }
+    }
+
+    pub fn get(&self, resource: usize) -> Option<RLimit> {
+        self.limits.get(resource).copied()
+    }
+
+    /// Soft limit of a resource, the one enforced.
+    pub fn cur(&self, resource: usize) -> usize {
+        self.limits[resource].cur
+    }
+
+    /// Replaces a limit, returns false if the resource is unknown, the soft limit is above the
+    /// hard one or the hard limit would be raised without being `privileged`.
+    pub fn set(&mut self, resource: usize, limit: RLimit, privileged: bool) -> bool {
+        let Some(old) = self.limits.get_mut(resource) else {
+            return false;
+        };
+        if limit.cur > limit.max || (limit.max > old.max && !privileged) {
+            return false;
+        }
+        *old = limit;
+        true
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn rlimit_test() {
+    use log::info;
+
+    let mut limits = ResourceLimits::new();
+    assert_eq!(limits.cur(RLIMIT_CORE), 0);
+    assert_eq!(limits.cur(RLIMIT_NOFILE), 1024);
+    assert_eq!(limits.cur(RLIMIT_AS), RLIM_INFINITY);
+    assert!(limits.get(RLIM_NLIMITS).is_none());
+
+    let limit = |cur, max| RLimit { cur, max };
+    assert!(!limits.set(RLIMIT_NOFILE, limit(64, 32), true));
+    assert!(!limits.set(RLIMIT_NOFILE, limit(64, 8192), false));
+    assert!(!limits.set(RLIM_NLIMITS, limit(0, 0), true));
+    assert!(limits.set(RLIMIT_NOFILE, limit(64, 128), false));
+    assert!(!limits.set(RLIMIT_NOFILE, limit(64, 256), false));
+    assert!(limits.set(RLIMIT_NOFILE, limit(64, 256), true));
+    assert_eq!(limits.cur(RLIMIT_NOFILE), 64);
+
+    info!("rlimit_test passed !");
+}
@@ -0,0 +1,132 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::Scheduler;
+use crate::task::task::TaskControlBlock;
+
+/// Woken tasks are placed at most half of this before `min_vruntime`.
+const SCHED_LATENCY_US: u64 = 6000;
+
+/// Runs the ready task with the least weighted runtime next.
+#[derive(Default)]
+pub struct CfsScheduler {
+    /// Keyed by `(vruntime, sequence)` so that equal vruntimes stay FIFO.
+    timeline: BTreeMap<(u64, usize), Arc<TaskControlBlock>>,
+    /// Lower bound of the vruntime of all ready and running tasks.
+    min_vruntime: u64,
+    seq: usize,
+}
+
+impl CfsScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn enqueue(&mut self, task: Arc<TaskControlBlock>, vruntime: u64) {
+        self.timeline.insert((vruntime, self.seq), task);
+        self.seq += 1;
+    }
+}
+
+impl Scheduler for CfsScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let mut inner = task.inner_exclusive_access();
+        // Not from 0, it would run until it caught up with everybody else.
+        if inner.sched.is_new() {
+            inner.sched.vruntime = inner.sched.vruntime.max(self.min_vruntime);
+        }
+        let vruntime = inner.sched.vruntime;
+        drop(inner);
+
+        self.enqueue(task, vruntime);
+    }
+
+    fn wakeup(&mut self, task: Arc<TaskControlBlock>) {
+        let mut inner = task.inner_exclusive_access();
+        let floor = self.min_vruntime.saturating_sub(SCHED_LATENCY_US / 2);
+        inner.sched.vruntime = inner.sched.vruntime.max(floor);
+        let vruntime = inner.sched.vruntime;
+        drop(inner);
+
+        self.enqueue(task, vruntime);
+    }
+
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let ((vruntime, _), task) = self.timeline.pop_first()?;
+        self.min_vruntime = self.min_vruntime.max(vruntime);
+        Some(task)
+    }
+
+    /// Leaves the vruntime relative to `min_vruntime`, `migrate` rebases it.
+    fn steal(&mut self, hart: usize) -> Option<Arc<TaskControlBlock>> {
+        let key = *self
+            .timeline
+            .iter()
+            .rev()
+            .find(|(_, task)| task.can_run_on(hart))?
+            .0;
+        let task = self.timeline.remove(&key)?;
+        task.inner_exclusive_access().sched.vruntime = key.0.saturating_sub(self.min_vruntime);
+        Some(task)
+    }
+
+    fn migrate(&mut self, task: Arc<TaskControlBlock>) {
+        let mut inner = task.inner_exclusive_access();
+        inner.sched.vruntime += self.min_vruntime;
+        let vruntime = inner.sched.vruntime;
+        drop(inner);
+
+        self.enqueue(task, vruntime);
+    }
+
+    fn queue_lens(&self) -> Vec<usize> {
+        vec![self.timeline.len()]
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn cfs_test() {
+    use log::info;
+
+    use super::{DEFAULT_PRIORITY, NICE_0_WEIGHT};
+    use crate::task::kthread::test_task;
+
+    let task = |vruntime| {
+        let task = test_task();
+        task.inner_exclusive_access().sched.vruntime = vruntime;
+        task
+    };
+    let vruntime = |task: &Arc<TaskControlBlock>| task.inner_exclusive_access().sched.vruntime;
+
+    let mut cfs = CfsScheduler::new();
+    [30000, 10000, 20000]
+        .into_iter()
+        .for_each(|v| cfs.add(task(v)));
+    for expected in [10000, 20000, 30000] {
+        assert_eq!(vruntime(&cfs.fetch().unwrap()), expected);
+    }
+    assert!(cfs.fetch().is_none());
+
+    let new = task(0);
+    cfs.add(new.clone());
+    assert_eq!(vruntime(&new), 30000);
+    cfs.fetch();
+    let sleeper = task(0);
+    sleeper.inner_exclusive_access().sched.exec_start = 1;
+    cfs.wakeup(sleeper.clone());
+    assert_eq!(vruntime(&sleeper), 30000 - SCHED_LATENCY_US / 2);
+    cfs.fetch();
+
+    let mut inner = sleeper.inner_exclusive_access();
+    inner.sched.set_priority(DEFAULT_PRIORITY + 5);
+    let start = inner.sched.vruntime;
+    let weight = inner.sched.weight;
+    inner.sched.update_curr(1 + weight as usize);
+    assert!(weight > NICE_0_WEIGHT);
+    assert_eq!(inner.sched.vruntime - start, NICE_0_WEIGHT);
+    drop(inner);
+
+    info!("cfs_test passed !");
+}
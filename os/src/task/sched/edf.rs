@@ -0,0 +1,234 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::config::MAX_HARTS;
+use crate::sync::SpinLock;
+use crate::task::task::TaskControlBlock;
+
+/// Share of each hart in parts per million that real-time tasks may reserve altogether.
+pub const RT_BANDWIDTH_PPM: usize = 950_000;
+
+/// Bandwidth reserved on each hart, EDF queues are per hart and real-time tasks never migrate.
+static RT_RESERVED: [SpinLock<usize>; MAX_HARTS] = [const { SpinLock::new(0) }; MAX_HARTS];
+
+/// Replaces a reservation of `old` bandwidth on `hart` by one of `new`, returns false and keeps
+/// the old one if the total of the hart would exceed `RT_BANDWIDTH_PPM`.
+pub fn admit_rt(hart: usize, old: usize, new: usize) -> bool {
+    let mut reserved = RT_RESERVED[hart].lock();
+    let total = *reserved - old + new;
+    if total > RT_BANDWIDTH_PPM {
+        return false;
+    }
+    *reserved = total;
+    true
+}
+
+pub fn release_rt(hart: usize, bandwidth: usize) {
+    *RT_RESERVED[hart].lock() -= bandwidth;
+}
+
+/// Reservation of a real-time task, all times are in microseconds.
+#[derive(Clone, Copy)]
+pub struct RtParams {
+    pub runtime: usize,
+    pub period: usize,
+    /// Deadline relative to the start of each period.
+    pub deadline: usize,
+    /// Start of the current period.
+    pub release: usize,
+    pub abs_deadline: usize,
+    /// Runtime left to the current job.
+    pub budget: usize,
+    /// The current job finished or used up its budget.
+    pub throttled: bool,
+    /// Hart the bandwidth is reserved on, the task runs there only.
+    pub hart: usize,
+}
+
+impl RtParams {
+    pub fn new(runtime: usize, period: usize, deadline: usize, now: usize, hart: usize) -> Self {
+        Self {
+            runtime,
+            period,
+            deadline,
+            release: now,
+            abs_deadline: now + deadline,
+            budget: runtime,
+            throttled: false,
+            hart,
+        }
+    }
+
+    pub fn bandwidth(&self) -> usize {
+        self.runtime * 1_000_000 / self.period
+    }
+
+    fn replenish(&mut self, release: usize) {
+        self.release = release;
+        self.abs_deadline = release + self.deadline;
+        self.budget = self.runtime;
+        self.throttled = false;
+    }
+
+    /// Starts the next job once its period began, returns whether it did.
+    fn release_due(&mut self, now: usize) -> bool {
+        let next = self.release + self.period;
+        if next > now {
+            return false;
+        }
+        // Periods which passed entirely while the task waited are skipped.
+        self.replenish(if next + self.period <= now { now } else { next });
+        true
+    }
+}
+
+/// Ready and throttled real-time tasks, scheduled earliest deadline first.
+#[derive(Default)]
+pub struct EdfQueue {
+    ready: BTreeMap<(usize, usize), Arc<TaskControlBlock>>,
+    throttled: Vec<Arc<TaskControlBlock>>,
+    seq: usize,
+}
+
+impl EdfQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ready.len() + self.throttled.len()
+    }
+
+    pub fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let inner = task.inner_exclusive_access();
+        let rt = inner.sched.rt.unwrap();
+        drop(inner);
+
+        if rt.throttled {
+            self.throttled.push(task);
+        } else {
+            self.ready.insert((rt.abs_deadline, self.seq), task);
+            self.seq += 1;
+        }
+    }
+
+    fn release_due(&mut self, now: usize) {
+        let mut i = 0;
+        while i < self.throttled.len() {
+            let mut inner = self.throttled[i].inner_exclusive_access();
+            let rt = inner.sched.rt.as_mut().unwrap();
+            if rt.release_due(now) {
+                let abs_deadline = rt.abs_deadline;
+                drop(inner);
+                let task = self.throttled.swap_remove(i);
+                self.ready.insert((abs_deadline, self.seq), task);
+                self.seq += 1;
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Start of the earliest next period among the throttled tasks.
+    pub fn next_release(&self) -> Option<usize> {
+        self.throttled
+            .iter()
+            .map(|task| {
+                let rt = task.inner_exclusive_access().sched.rt.unwrap();
+                rt.release + rt.period
+            })
+            .min()
+    }
+
+    /// Earliest deadline among the ready tasks, after releasing the jobs whose period began.
+    pub fn earliest_deadline(&mut self, now: usize) -> Option<usize> {
+        self.release_due(now);
+        self.ready
+            .first_key_value()
+            .map(|(&(deadline, _), _)| deadline)
+    }
+
+    pub fn fetch(&mut self, now: usize) -> Option<Arc<TaskControlBlock>> {
+        self.release_due(now);
+        let (_, task) = self.ready.pop_first()?;
+
+        let mut inner = task.inner_exclusive_access();
+        let se = &mut inner.sched;
+        let rt = se.rt.as_mut().unwrap();
+        if now > rt.abs_deadline {
+            // The job never got to run before its deadline, it starts over from now.
+            rt.replenish(now);
+            se.deadline_misses += 1;
+        }
+        drop(inner);
+
+        Some(task)
+    }
+
+    /// Charges the running task, returns whether it has to give up the hart.
+    pub fn tick(&mut self, current: &Arc<TaskControlBlock>, now: usize) -> bool {
+        let mut inner = current.inner_exclusive_access();
+        let se = &mut inner.sched;
+        let delta = se.update_curr(now);
+        let rt = se.rt.as_mut().unwrap();
+        rt.budget = rt.budget.saturating_sub(delta);
+
+        if now > rt.abs_deadline {
+            rt.replenish(now);
+            se.deadline_misses += 1;
+        } else if rt.budget == 0 {
+            rt.throttled = true;
+            return true;
+        }
+        let abs_deadline = rt.abs_deadline;
+        drop(inner);
+
+        self.earliest_deadline(now)
+            .is_some_and(|deadline| deadline < abs_deadline)
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn edf_test() {
+    use log::info;
+
+    use crate::task::kthread::test_task;
+
+    // A full hart does not keep another one from admitting.
+    let other = MAX_HARTS - 1;
+    assert!(admit_rt(0, 0, RT_BANDWIDTH_PPM));
+    assert!(!admit_rt(0, 0, 1));
+    assert!(admit_rt(other, 0, 1));
+    assert!(admit_rt(0, RT_BANDWIDTH_PPM, 1));
+    release_rt(0, 1);
+    release_rt(other, 1);
+
+    let now = 1_000_000;
+    let task = |runtime, period, deadline| {
+        let task = test_task();
+        task.inner_exclusive_access().sched.rt =
+            Some(RtParams::new(runtime, period, deadline, now, 0));
+        task
+    };
+    let (late, early) = (task(100, 10_000, 5_000), task(100, 10_000, 1_000));
+    let mut edf = EdfQueue::new();
+    edf.add(late.clone());
+    edf.add(early.clone());
+    assert_eq!(edf.earliest_deadline(now), Some(now + 1_000));
+    let running = edf.fetch(now).unwrap();
+    assert!(Arc::ptr_eq(&running, &early));
+
+    // Using up the budget throttles the task until its next period.
+    running.inner_exclusive_access().sched.exec_start = now;
+    assert!(edf.tick(&running, now + 100));
+    edf.add(running);
+    assert_eq!(edf.next_release(), Some(now + 10_000));
+    assert!(Arc::ptr_eq(&edf.fetch(now + 200).unwrap(), &late));
+    assert!(edf.fetch(now + 200).is_none());
+    assert!(Arc::ptr_eq(&edf.fetch(now + 10_000).unwrap(), &early));
+    let rt = early.inner_exclusive_access().sched.rt.unwrap();
+    assert_eq!((rt.abs_deadline, rt.budget), (now + 11_000, 100));
+
+    info!("edf_test passed !");
+}
@@ -0,0 +1,163 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::{steal_from_back, Scheduler};
+use crate::task::task::TaskControlBlock;
+
+pub const MLFQ_LEVELS: usize = 4;
+
+/// Ticks after which all tasks go back to the top queue, so that sunk ones cannot starve.
+const BOOST_INTERVAL_TICKS: usize = 100;
+
+/// Time quantum of a queue in timer ticks.
+fn quantum(level: usize) -> usize {
+    1 << level
+}
+
+/// Multi-level feedback queues, level 0 first. A task using up its quantum sinks a level, one
+/// giving up the hart early rises a level.
+pub struct MlfqScheduler {
+    queues: [VecDeque<Arc<TaskControlBlock>>; MLFQ_LEVELS],
+    ticks: usize,
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            ticks: 0,
+        }
+    }
+
+    fn boost(&mut self) {
+        for level in 1..MLFQ_LEVELS {
+            while let Some(task) = self.queues[level].pop_front() {
+                let mut inner = task.inner_exclusive_access();
+                inner.sched.mlfq_level = 0;
+                inner.sched.mlfq_ticks = 0;
+                drop(inner);
+                self.queues[0].push_back(task);
+            }
+        }
+    }
+
+    fn has_ready_above(&self, level: usize) -> bool {
+        self.queues[..level].iter().any(|queue| !queue.is_empty())
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let mut inner = task.inner_exclusive_access();
+        let se = &mut inner.sched;
+        if se.mlfq_preempted {
+            se.mlfq_preempted = false;
+        } else if !se.is_new() {
+            // Gave up the hart before its quantum ran out: yielded or blocked.
+            se.mlfq_level = se.mlfq_level.saturating_sub(1);
+            se.mlfq_ticks = 0;
+        }
+        let level = se.mlfq_level;
+        drop(inner);
+
+        self.queues[level].push_back(task);
+    }
+
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+
+    fn tick(&mut self, current: &Arc<TaskControlBlock>) -> bool {
+        self.ticks += 1;
+        let boosted = self.ticks.is_multiple_of(BOOST_INTERVAL_TICKS);
+        if boosted {
+            self.boost();
+        }
+
+        let mut inner = current.inner_exclusive_access();
+        let se = &mut inner.sched;
+        if boosted {
+            se.mlfq_level = 0;
+            se.mlfq_ticks = 0;
+        }
+
+        se.mlfq_ticks += 1;
+        if se.mlfq_ticks >= quantum(se.mlfq_level) {
+            se.mlfq_level = (se.mlfq_level + 1).min(MLFQ_LEVELS - 1);
+            se.mlfq_ticks = 0;
+            se.mlfq_preempted = true;
+            return true;
+        }
+
+        // A higher queue got a task, it runs first but the current one keeps its level.
+        let level = se.mlfq_level;
+        if self.has_ready_above(level) {
+            se.mlfq_preempted = true;
+            return true;
+        }
+        false
+    }
+
+    /// Takes from the lowest queue first.
+    fn steal(&mut self, hart: usize) -> Option<Arc<TaskControlBlock>> {
+        self.queues
+            .iter_mut()
+            .rev()
+            .find_map(|queue| steal_from_back(queue, hart))
+    }
+
+    fn migrate(&mut self, task: Arc<TaskControlBlock>) {
+        let level = task.inner_exclusive_access().sched.mlfq_level;
+        self.queues[level].push_back(task);
+    }
+
+    fn queue_lens(&self) -> Vec<usize> {
+        self.queues.iter().map(|queue| queue.len()).collect()
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn mlfq_test() {
+    use log::info;
+
+    use crate::task::kthread::test_task;
+
+    let level = |task: &Arc<TaskControlBlock>| task.inner_exclusive_access().sched.mlfq_level;
+    let mut mlfq = MlfqScheduler::new();
+    let (cpu, io) = (test_task(), test_task());
+    cpu.inner_exclusive_access().sched.exec_start = 1;
+    io.inner_exclusive_access().sched.exec_start = 1;
+
+    // Using up the quantum sinks a task, giving up the hart early raises it.
+    assert!(mlfq.tick(&cpu));
+    mlfq.add(cpu.clone());
+    assert_eq!(level(&cpu), 1);
+    io.inner_exclusive_access().sched.mlfq_level = 2;
+    mlfq.add(io.clone());
+    assert_eq!(level(&io), 1);
+    assert!(Arc::ptr_eq(&mlfq.fetch().unwrap(), &cpu));
+    assert!(Arc::ptr_eq(&mlfq.fetch().unwrap(), &io));
+
+    // A task ready in a higher queue preempts without demoting.
+    io.inner_exclusive_access().sched.mlfq_level = 0;
+    io.inner_exclusive_access().sched.mlfq_preempted = true;
+    mlfq.add(io.clone());
+    assert!(mlfq.tick(&cpu));
+    assert_eq!(level(&cpu), 1);
+    mlfq.fetch();
+
+    // The boost brings sunk tasks back to the top queue.
+    io.inner_exclusive_access().sched.mlfq_level = MLFQ_LEVELS - 1;
+    io.inner_exclusive_access().sched.mlfq_preempted = true;
+    mlfq.add(io.clone());
+    while !(mlfq.ticks + 1).is_multiple_of(BOOST_INTERVAL_TICKS) {
+        mlfq.tick(&cpu);
+    }
+    assert_eq!(level(&io), MLFQ_LEVELS - 1);
+    mlfq.tick(&cpu);
+    assert_eq!(level(&io), 0);
+    assert_eq!(mlfq.queue_lens()[0], 1);
+
+    info!("mlfq_test passed !");
+}
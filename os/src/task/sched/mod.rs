@@ -0,0 +1,180 @@
+mod cfs;
+mod edf;
+mod mlfq;
+mod stride;
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+#[cfg(feature = "selftest")]
+pub use cfs::cfs_test;
+pub use cfs::CfsScheduler;
+#[cfg(feature = "selftest")]
+pub use edf::edf_test;
+pub use edf::{admit_rt, release_rt, EdfQueue, RtParams};
+#[cfg(feature = "selftest")]
+pub use mlfq::mlfq_test;
+pub use mlfq::MlfqScheduler;
+pub use stride::StrideScheduler;
+
+use super::task::TaskControlBlock;
+
+pub const DEFAULT_PRIORITY: u8 = 16;
+
+/// Load weight of a task running at nice 0.
+pub const NICE_0_WEIGHT: u64 = 1024;
+
+/// Linux's `sched_prio_to_weight`, indexed by `nice + 20`.
+const PRIO_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+
+/// The default priority is nice 0, every step above it one nice step lower.
+fn priority_to_weight(priority: u8) -> u64 {
+    let nice = (DEFAULT_PRIORITY as isize - priority as isize).clamp(-20, 19);
+    PRIO_TO_WEIGHT[(nice + 20) as usize]
+}
+
+/// Per-task bookkeeping shared by all scheduling policies.
+pub struct SchedEntity {
+    /// Priority the policies schedule the task with.
+    pub priority: u8,
+    /// Priority set with `sys_set_priority`.
+    pub base_priority: u8,
+    /// Highest priority of the tasks waiting for a mutex the task holds, 0 if none.
+    pub inherited_priority: u8,
+    pub stride: u8,
+    pub weight: u64,
+    /// Weighted runtime in microseconds.
+    pub vruntime: u64,
+    /// When the task was last switched in, 0 if it never ran.
+    pub exec_start: usize,
+    pub sum_exec_runtime: usize,
+    /// MLFQ queue the task belongs to, 0 being the highest priority.
+    pub mlfq_level: usize,
+    /// Timer ticks consumed from the quantum of the current MLFQ level.
+    pub mlfq_ticks: usize,
+    /// Set when the MLFQ took the hart away, as opposed to the task giving it up.
+    pub mlfq_preempted: bool,
+    /// Reservation of the real-time class, `None` for normal tasks.
+    pub rt: Option<RtParams>,
+    pub deadline_misses: usize,
+    /// Hart the task last ran on.
+    pub hart: usize,
+}
+
+impl SchedEntity {
+    pub fn new() -> Self {
+        Self {
+            priority: DEFAULT_PRIORITY,
+            base_priority: DEFAULT_PRIORITY,
+            inherited_priority: 0,
+            stride: 0,
+            weight: NICE_0_WEIGHT,
+            vruntime: 0,
+            exec_start: 0,
+            sum_exec_runtime: 0,
+            mlfq_level: 0,
+            mlfq_ticks: 0,
+            mlfq_preempted: false,
+            rt: None,
+            deadline_misses: 0,
+            hart: 0,
+        }
+    }
+
+    pub fn set_priority(&mut self, priority: u8) {
+        self.base_priority = priority;
+        self.update_priority();
+    }
+
+    /// Lends the task `priority`, returns whether the priority it runs with changed.
+    pub fn set_inherited_priority(&mut self, priority: u8) -> bool {
+        self.inherited_priority = priority;
+        self.update_priority()
+    }
+
+    fn update_priority(&mut self) -> bool {
+        let priority = self.base_priority.max(self.inherited_priority);
+        let changed = priority != self.priority;
+        self.priority = priority;
+        self.weight = priority_to_weight(priority);
+        changed
+    }
+
+    pub fn is_new(&self) -> bool {
+        self.exec_start == 0
+    }
+
+    /// Charges the time since the last update to the task, returns it.
+    pub fn update_curr(&mut self, now: usize) -> usize {
+        let delta = now.saturating_sub(self.exec_start);
+        self.sum_exec_runtime += delta;
+        self.vruntime += delta as u64 * NICE_0_WEIGHT / self.weight;
+        self.exec_start = now;
+        delta
+    }
+
+    /// Ends the current job of a real-time task, it is not runnable again before its next period.
+    pub fn complete_rt_job(&mut self, now: usize) {
+        if let Some(rt) = self.rt.as_mut() {
+            if now > rt.abs_deadline {
+                self.deadline_misses += 1;
+            }
+            rt.throttled = true;
+        }
+    }
+}
+
+/// A policy deciding which ready task runs next.
+pub trait Scheduler: Send {
+    /// Queues a task that gave up the hart while still runnable.
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+
+    /// Queues a task that has just left a blocked state.
+    fn wakeup(&mut self, task: Arc<TaskControlBlock>) {
+        self.add(task);
+    }
+
+    /// Removes and returns the task that should run next.
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>>;
+
+    /// Returns whether `current` should be preempted.
+    fn tick(&mut self, _current: &Arc<TaskControlBlock>) -> bool {
+        true
+    }
+
+    /// Removes a ready task allowed on `hart` for it to take over, the one that would run last.
+    fn steal(&mut self, hart: usize) -> Option<Arc<TaskControlBlock>>;
+
+    /// Queues a task taken over from the scheduler of another hart.
+    fn migrate(&mut self, task: Arc<TaskControlBlock>) {
+        self.add(task);
+    }
+
+    /// Number of ready tasks in each queue of the policy, highest priority first.
+    fn queue_lens(&self) -> Vec<usize>;
+}
+
+/// Removes the last task of `queue` that is allowed to run on `hart`.
+fn steal_from_back(
+    queue: &mut VecDeque<Arc<TaskControlBlock>>,
+    hart: usize,
+) -> Option<Arc<TaskControlBlock>> {
+    let index = queue.iter().rposition(|task| task.can_run_on(hart))?;
+    queue.remove(index)
+}
+
+/// Builds the scheduler selected by the `SCHED` environment variable at compile time,
+/// CFS unless it names `stride` or `mlfq`.
+pub fn new_scheduler() -> Box<dyn Scheduler> {
+    match option_env!("SCHED") {
+        Some("STRIDE") | Some("stride") => Box::new(StrideScheduler::new()),
+        Some("MLFQ") | Some("mlfq") => Box::new(MlfqScheduler::new()),
+        _ => Box::new(CfsScheduler::new()),
+    }
+}
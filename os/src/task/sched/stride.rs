@@ -0,0 +1,62 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::{steal_from_back, Scheduler};
+use crate::config::BIG_STRIDE;
+use crate::task::task::TaskControlBlock;
+
+#[derive(Default)]
+pub struct StrideScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        if self.ready_queue.is_empty() {
+            return None;
+        }
+        let mut min_stride = self
+            .ready_queue
+            .front()
+            .unwrap()
+            .inner_exclusive_access()
+            .sched
+            .stride;
+
+        let mut index = 0;
+
+        for (i, task) in self.ready_queue.iter().enumerate() {
+            let inner = task.inner_exclusive_access();
+            if inner.sched.stride < min_stride {
+                min_stride = inner.sched.stride;
+                index = i;
+            }
+        }
+
+        let task = self.ready_queue.remove(index)?;
+        let mut inner = task.inner_exclusive_access();
+        inner.sched.stride += BIG_STRIDE / inner.sched.priority;
+        drop(inner);
+        Some(task)
+    }
+
+    fn steal(&mut self, hart: usize) -> Option<Arc<TaskControlBlock>> {
+        steal_from_back(&mut self.ready_queue, hart)
+    }
+
+    fn queue_lens(&self) -> Vec<usize> {
+        vec![self.ready_queue.len()]
+    }
+}
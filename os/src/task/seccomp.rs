@@ -0,0 +1,211 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// Filters a task can have installed, and rules a filter can have.
+const MAX_FILTERS: usize = 16;
+const MAX_FILTER_RULES: usize = 256;
+
+/// Value of `FilterRule::arg` for rules that match whatever the arguments are.
+pub const FILTER_ANY_ARG: usize = usize::MAX;
+
+/// What happens to a syscall, ordered from the most permissive.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FilterAction {
+    Allow,
+    /// Fail the syscall with -1 without running it.
+    Error,
+    /// Exit the task with `-SIGSYS`.
+    Kill,
+}
+
+impl FilterAction {
+    fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Allow),
+            1 => Some(Self::Error),
+            2 => Some(Self::Kill),
+            _ => None,
+        }
+    }
+}
+
+/// How a rule compares a syscall argument with its value.
+#[derive(Clone, Copy)]
+enum ArgCmp {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    /// All bits of the value are set in the argument.
+    AllSet,
+    /// Any bit of the value is set in the argument.
+    AnySet,
+}
+
+impl ArgCmp {
+    fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Eq),
+            1 => Some(Self::Ne),
+            2 => Some(Self::Lt),
+            3 => Some(Self::Ge),
+            4 => Some(Self::AllSet),
+            5 => Some(Self::AnySet),
+            _ => None,
+        }
+    }
+
+    fn matches(self, arg: usize, value: usize) -> bool {
+        match self {
+            Self::Eq => arg == value,
+            Self::Ne => arg != value,
+            Self::Lt => arg < value,
+            Self::Ge => arg >= value,
+            Self::AllSet => arg & value == value,
+            Self::AnySet => arg & value != 0,
+        }
+    }
+}
+
+/// One rule of a filter as passed to `seccomp`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FilterRule {
+    pub syscall_id: usize,
+    pub action: usize,
+    pub arg: usize,
+    pub cmp: usize,
+    pub value: usize,
+}
+
+struct Rule {
+    syscall_id: usize,
+    action: FilterAction,
+    predicate: Option<(usize, ArgCmp, usize)>,
+}
+
+impl Rule {
+    fn parse(rule: &FilterRule) -> Option<Self> {
+        let predicate = if rule.arg == FILTER_ANY_ARG {
+            None
+        } else if rule.arg < 6 {
+            Some((rule.arg, ArgCmp::from_raw(rule.cmp)?, rule.value))
+        } else {
+            return None;
+        };
+        Some(Self {
+            syscall_id: rule.syscall_id,
+            action: FilterAction::from_raw(rule.action)?,
+            predicate,
+        })
+    }
+
+    fn matches(&self, syscall_id: usize, args: &[usize; 6]) -> bool {
+        syscall_id == self.syscall_id
+            && self
+                .predicate
+                .is_none_or(|(arg, cmp, value)| cmp.matches(args[arg], value))
+    }
+}
+
+/// Rules checked in order, the first matching one decides, `default` if none does.
+pub struct SyscallFilter {
+    rules: Vec<Rule>,
+    default: FilterAction,
+}
+
+impl SyscallFilter {
+    /// Builds a filter from the rules passed to `seccomp`, `None` if they are invalid.
+    pub fn parse(rules: impl ExactSizeIterator<Item = FilterRule>, default: usize) -> Option<Self> {
+        if rules.len() > MAX_FILTER_RULES {
+            return None;
+        }
+        Some(Self {
+            rules: rules
+                .map(|rule| Rule::parse(&rule))
+                .collect::<Option<_>>()?,
+            default: FilterAction::from_raw(default)?,
+        })
+    }
+
+    fn check(&self, syscall_id: usize, args: &[usize; 6]) -> FilterAction {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(syscall_id, args))
+            .map_or(self.default, |rule| rule.action)
+    }
+}
+
+/// Filters installed by a task and its ancestors, the strictest action among them applies.
+#[derive(Clone, Default)]
+pub struct SyscallFilters(Vec<Arc<SyscallFilter>>);
+
+impl SyscallFilters {
+    /// Adds a filter, returns false if there are too many already.
+    pub fn install(&mut self, filter: SyscallFilter) -> bool {
+        if self.0.len() == MAX_FILTERS {
+            return false;
+        }
+        self.0.push(Arc::new(filter));
+        true
+    }
+
+    pub fn check(&self, syscall_id: usize, args: &[usize; 6]) -> FilterAction {
+        self.0
+            .iter()
+            .map(|filter| filter.check(syscall_id, args))
+            .max()
+            .unwrap_or(FilterAction::Allow)
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn seccomp_test() {
+    use log::info;
+    let rule = |syscall_id, action, arg, cmp, value| FilterRule {
+        syscall_id,
+        action,
+        arg,
+        cmp,
+        value,
+    };
+    let parse =
+        |rules: &[FilterRule], default| SyscallFilter::parse(rules.iter().copied(), default);
+
+    // Malformed rules and too many of them are refused.
+    assert!(parse(&[rule(64, 3, FILTER_ANY_ARG, 0, 0)], 0).is_none());
+    assert!(parse(&[rule(64, 0, 6, 0, 0)], 0).is_none());
+    assert!(parse(&[rule(64, 0, 0, 6, 0)], 0).is_none());
+    assert!(parse(&[], 3).is_none());
+    assert!(parse(
+        &[rule(64, 0, FILTER_ANY_ARG, 0, 0); MAX_FILTER_RULES + 1],
+        0
+    )
+    .is_none());
+
+    // The first matching rule decides, the default applies if none does.
+    let filter = parse(
+        &[
+            rule(64, 0, 0, 0, 1),
+            rule(64, 1, FILTER_ANY_ARG, 0, 0),
+            rule(220, 2, 0, 4, 0b11),
+        ],
+        0,
+    )
+    .unwrap();
+    let mut filters = SyscallFilters::default();
+    assert!(filters.install(filter));
+    assert!(filters.check(64, &[1, 0, 0, 0, 0, 0]) == FilterAction::Allow);
+    assert!(filters.check(64, &[2, 0, 0, 0, 0, 0]) == FilterAction::Error);
+    assert!(filters.check(220, &[0b01, 0, 0, 0, 0, 0]) == FilterAction::Allow);
+    assert!(filters.check(220, &[0b111, 0, 0, 0, 0, 0]) == FilterAction::Kill);
+
+    // A later filter can only make things stricter.
+    assert!(filters.install(parse(&[rule(64, 0, FILTER_ANY_ARG, 0, 0)], 1).unwrap()));
+    assert!(filters.check(64, &[2, 0, 0, 0, 0, 0]) == FilterAction::Error);
+    assert!(filters.check(93, &[0; 6]) == FilterAction::Error);
+    while filters.install(parse(&[], 0).unwrap()) {}
+    assert_eq!(filters.0.len(), MAX_FILTERS);
+
+    info!("seccomp_test passed !");
+}
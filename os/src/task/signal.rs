@@ -0,0 +1,227 @@
+use alloc::sync::Arc;
+
+use super::manager::wakeup_task;
+use super::processor::current_task;
+use super::ptrace::{end_trace_stop, ptrace_stop};
+use super::task::{TaskControlBlock, TaskStatus};
+use super::{exit_current_and_run_next, stop_current_and_run_next, INITPROC};
+use crate::sync::WakeReason;
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGBUS: usize = 7;
+pub const SIGKILL: usize = 9;
+pub const SIGSEGV: usize = 11;
+pub const SIGPIPE: usize = 13;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGURG: usize = 23;
+pub const SIGXCPU: usize = 24;
+pub const SIGWINCH: usize = 28;
+pub const SIGSYS: usize = 31;
+
+/// Signals are numbered from 1 up to below this.
+pub const NSIG: usize = 64;
+
+/// What a signal does to a task, there are no user handlers.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SignalAction {
+    /// Exit with the negated signal number as exit code.
+    Terminate,
+    Stop,
+    Continue,
+    Ignore,
+}
+
+pub fn default_action(sig: usize) -> SignalAction {
+    match sig {
+        SIGCHLD | SIGURG | SIGWINCH => SignalAction::Ignore,
+        SIGCONT => SignalAction::Continue,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => SignalAction::Stop,
+        _ => SignalAction::Terminate,
+    }
+}
+
+fn is_stop_signal(sig: usize) -> bool {
+    default_action(sig) == SignalAction::Stop
+}
+
+/// Pending signals of a task, one bit per signal.
+#[derive(Clone, Copy, Default)]
+pub struct SignalSet(u64);
+
+impl SignalSet {
+    pub fn contains(&self, sig: usize) -> bool {
+        self.0 & (1 << sig) != 0
+    }
+
+    pub fn insert(&mut self, sig: usize) {
+        self.0 |= 1 << sig;
+    }
+
+    pub fn remove(&mut self, sig: usize) {
+        self.0 &= !(1 << sig);
+    }
+
+    /// Takes the next signal to act on, `SIGKILL` before all others and then by number.
+    pub fn take_next(&mut self) -> Option<usize> {
+        let sig = if self.contains(SIGKILL) {
+            SIGKILL
+        } else if self.0 != 0 {
+            self.0.trailing_zeros() as usize
+        } else {
+            return None;
+        };
+        self.remove(sig);
+        Some(sig)
+    }
+
+    /// Whether a pending signal will terminate or stop the task.
+    pub fn interrupts(&self) -> bool {
+        (1..NSIG).any(|sig| {
+            self.contains(sig)
+                && matches!(
+                    default_action(sig),
+                    SignalAction::Terminate | SignalAction::Stop
+                )
+        })
+    }
+}
+
+/// Makes `sig` pending for `task`, which acts on it on its way back to user mode. `SIGCONT` and
+/// `SIGKILL` wake a stopped task up, signals that terminate or stop it end an interruptible sleep.
+/// The initproc takes none.
+pub fn send_signal(task: &Arc<TaskControlBlock>, sig: usize) {
+    if Arc::ptr_eq(task, &INITPROC) || default_action(sig) == SignalAction::Ignore {
+        return;
+    }
+
+    let mut inner = task.inner_exclusive_access();
+    if inner.is_zombie() {
+        return;
+    }
+    let mut wake = false;
+    if sig == SIGCONT || sig == SIGKILL {
+        (1..NSIG)
+            .filter(|&sig| is_stop_signal(sig))
+            .for_each(|sig| inner.signals.remove(sig));
+        // Only a task that committed to stopping is off the ready queues.
+        if inner.stop_signal.take().is_some() {
+            inner.task_status = TaskStatus::Ready;
+            wake = true;
+        }
+    } else if is_stop_signal(sig) {
+        inner.signals.remove(SIGCONT);
+    }
+    if sig == SIGKILL && end_trace_stop(&mut inner) {
+        wake = true;
+    }
+    inner.signals.insert(sig);
+    let sleeper = if inner.signals.interrupts() {
+        inner.sleeper.take()
+    } else {
+        None
+    };
+    drop(inner);
+
+    if wake {
+        wakeup_task(task.clone());
+    }
+    if let Some(sleeper) = sleeper {
+        sleeper.wake(WakeReason::Interrupted);
+    }
+}
+
+/// Acts on the signals pending for the running task, on its way back to user mode.
+pub fn handle_signals() {
+    loop {
+        let task = current_task().unwrap();
+        let sig = task.inner_exclusive_access().signals.take_next();
+        drop(task);
+        let Some(sig) = sig else {
+            return;
+        };
+        let sig = if sig == SIGKILL {
+            sig
+        } else {
+            match ptrace_stop(sig) {
+                Some(0) => continue,
+                Some(resumed) => resumed,
+                None => sig,
+            }
+        };
+        match default_action(sig) {
+            SignalAction::Terminate => exit_current_and_run_next(-(sig as i32)),
+            SignalAction::Stop => stop_current_and_run_next(sig),
+            SignalAction::Continue | SignalAction::Ignore => {}
+        }
+    }
+}
+
+/// Whether the running task has a signal pending that should interrupt a blocking syscall.
+pub fn current_has_pending_signal() -> bool {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .signals
+        .interrupts()
+}
+
+#[cfg(feature = "selftest")]
+pub fn signal_test() {
+    use log::info;
+
+    use super::job::{kill_pgrp, tasks_in_pgrp};
+    use super::kthread::test_task;
+    use super::manager::{insert_into_pid2task, remove_from_pid2task};
+
+    let mut set = SignalSet::default();
+    [SIGCHLD, SIGTSTP, SIGKILL, SIGINT]
+        .into_iter()
+        .for_each(|sig| set.insert(sig));
+    let order: [Option<usize>; 5] = core::array::from_fn(|_| set.take_next());
+    assert_eq!(
+        order,
+        [
+            Some(SIGKILL),
+            Some(SIGINT),
+            Some(SIGCHLD),
+            Some(SIGTSTP),
+            None
+        ]
+    );
+    set.insert(SIGCHLD);
+    assert!(!set.interrupts());
+    set.insert(SIGTSTP);
+    assert!(set.interrupts());
+
+    // Stopping and continuing signals cancel each other, ignored ones are dropped.
+    let task = test_task();
+    let signals = || task.inner_exclusive_access().signals.0;
+    send_signal(&task, SIGCHLD);
+    assert_eq!(signals(), 0);
+    send_signal(&task, SIGTSTP);
+    send_signal(&task, SIGCONT);
+    assert_eq!(signals(), 1 << SIGCONT);
+    send_signal(&task, SIGSTOP);
+    assert_eq!(signals(), 1 << SIGSTOP);
+
+    // Process groups are found through the live tasks.
+    let pgid = task.getpid();
+    task.inner_exclusive_access().pgid = pgid;
+    assert!(!kill_pgrp(pgid, SIGINT));
+    insert_into_pid2task(pgid, task.clone());
+    assert_eq!(tasks_in_pgrp(pgid).len(), 1);
+    assert!(kill_pgrp(pgid, SIGINT));
+    remove_from_pid2task(pgid);
+    assert!(task.inner_exclusive_access().signals.contains(SIGINT));
+
+    info!("signal_test passed !");
+}
@@ -1,29 +1,45 @@
+use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
-use core::cell::RefMut;
+use core::sync::atomic::AtomicBool;
 
+use super::clone::CloneFlags;
 use super::context::TaskContext;
+use super::cred::Credentials;
+use super::kthread::KernelThreadEntry;
+use super::manager::wakeup_task;
 use super::pid::{kstack_alloc, pid_alloc, KernelStack, PidHandle};
-use crate::config::{MAX_SYSCALL_NUM, TRAP_CONTEXT};
-use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPSafeCell;
+use super::ptrace::TraceState;
+use super::rlimit::{ResourceLimits, RLIMIT_AS, RLIMIT_STACK};
+use super::sched::SchedEntity;
+use super::seccomp::SyscallFilters;
+use super::signal::SignalSet;
+use super::usage::ResourceUsage;
+use crate::config::{ALL_HARTS_MASK, MAX_SYSCALL_NUM, PAGE_SIZE, TRAP_CONTEXT, USER_STACK_SIZE};
+use crate::fs::FdTable;
+use crate::mm::{MapPermission, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::sync::{MutexState, Sleeper, SpinLock, SpinLockGuard, SyncTable};
 use crate::timer::get_time_us;
-use crate::trap::{trap_handler, TrapContext};
+use crate::trap::{forget_fp_owner, save_fp_state, trap_handler, TrapContext};
 
 #[allow(unused)]
+#[repr(C)]
 pub struct TaskInfo {
     status: TaskStatus,
     syscall_times: [u32; MAX_SYSCALL_NUM],
     time: usize,
+    deadline_misses: usize,
 }
 pub struct TaskControlBlock {
     pub pid: PidHandle,
     pub kernel_stack: KernelStack,
-    inner: UPSafeCell<TaskControlBlockInner>,
+    /// Set while a hart runs the task, cleared once its context was saved by `__switch`.
+    pub on_cpu: AtomicBool,
+    inner: SpinLock<TaskControlBlockInner>,
 }
 
 impl TaskControlBlock {
-    pub fn new(elf_data: &[u8]) -> Self {
+    pub fn new(name: &str, elf_data: &[u8]) -> Self {
         let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
@@ -31,38 +47,60 @@ impl TaskControlBlock {
             .ppn();
 
         let pid_handle = pid_alloc();
+        // The first task leads the first session and process group.
+        let pid = pid_handle.0;
         let kernel_stack = kstack_alloc();
         let kstack_top = kernel_stack.get_top();
 
         let task_control_block = Self {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    task_status: TaskStatus::Ready,
-                    task_cx: TaskContext::goto_trap_return(kstack_top),
-                    start_time: 0,
-                    memory_set,
-                    parent: None,
-                    children: Default::default(),
-                    trap_cx_ppn,
-                    base_size: user_sp,
-                    program_brk: user_sp,
-                    exit_code: 0,
-                    stride: 0,
-                    priority: 16,
-                    heap_bottom: user_sp,
-                    syscall_times: [0; MAX_SYSCALL_NUM],
-                })
-            },
+            on_cpu: AtomicBool::new(false),
+            inner: SpinLock::new(TaskControlBlockInner {
+                task_status: TaskStatus::Ready,
+                task_cx: TaskContext::goto_trap_return(kstack_top),
+                start_time: 0,
+                mm: UserSpace::new(memory_set, user_sp),
+                sync: SyncTable::new(),
+                fd_table: FdTable::new(),
+                parent: None,
+                children: Default::default(),
+                trap_cx_ppn,
+                trap_cx_va: TRAP_CONTEXT,
+                base_size: user_sp,
+                exit_code: 0,
+                sched: SchedEntity::new(),
+                pi_held: vec::Vec::new(),
+                pi_blocked_on: None,
+                usage: ResourceUsage::new(),
+                children_usage: ResourceUsage::new(),
+                syscall_times: [0; MAX_SYSCALL_NUM],
+                affinity: ALL_HARTS_MASK,
+                rlimits: ResourceLimits::new(),
+                kthread_entry: None,
+                is_kthread: false,
+                vfork_parent: None,
+                pgid: pid,
+                sid: pid,
+                cred: Credentials::root(),
+                name: String::from(name),
+                filters: SyscallFilters::default(),
+                ptrace: None,
+                signals: SignalSet::default(),
+                stop_signal: None,
+                stop_reported: false,
+                sleeper: None,
+                last_scheduled: 0,
+            }),
         };
 
+        task_control_block.inner_exclusive_access().update_rss();
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
 
         *trap_cx = TrapContext::app_init_context(
             entry_point,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             kstack_top,
             trap_handler as usize,
         );
@@ -70,83 +108,220 @@ impl TaskControlBlock {
         task_control_block
     }
 
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
-        self.inner.exclusive_access()
+    /// Creates a kernel thread running `entry`, in `KERNEL_SPACE` and without a trap context.
+    pub fn new_kernel_thread(entry: KernelThreadEntry) -> Self {
+        let pid_handle = pid_alloc();
+        let kernel_stack = kstack_alloc();
+        let kstack_top = kernel_stack.get_top();
+
+        Self {
+            pid: pid_handle,
+            kernel_stack,
+            on_cpu: AtomicBool::new(false),
+            inner: SpinLock::new(TaskControlBlockInner {
+                task_status: TaskStatus::Ready,
+                task_cx: TaskContext::goto_kernel_thread_entry(kstack_top),
+                start_time: 0,
+                mm: UserSpace::new(MemorySet::new_bare(), 0),
+                sync: SyncTable::new(),
+                fd_table: FdTable::new(),
+                parent: None,
+                children: Default::default(),
+                trap_cx_ppn: PhysPageNum(0),
+                trap_cx_va: 0,
+                base_size: 0,
+                exit_code: 0,
+                sched: SchedEntity::new(),
+                pi_held: vec::Vec::new(),
+                pi_blocked_on: None,
+                usage: ResourceUsage::new(),
+                children_usage: ResourceUsage::new(),
+                syscall_times: [0; MAX_SYSCALL_NUM],
+                affinity: ALL_HARTS_MASK,
+                rlimits: ResourceLimits::new(),
+                kthread_entry: Some(entry),
+                is_kthread: true,
+                vfork_parent: None,
+                pgid: 0,
+                sid: 0,
+                cred: Credentials::root(),
+                name: String::from("kthread"),
+                filters: SyscallFilters::default(),
+                ptrace: None,
+                signals: SignalSet::default(),
+                stop_signal: None,
+                stop_reported: false,
+                sleeper: None,
+                last_scheduled: 0,
+            }),
+        }
+    }
+
+    pub fn inner_exclusive_access(&self) -> SpinLockGuard<'_, TaskControlBlockInner> {
+        self.inner.lock()
     }
 
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
 
+    pub fn can_run_on(&self, hart: usize) -> bool {
+        self.inner_exclusive_access().affinity & (1 << hart) != 0
+    }
+
     pub fn change_program_brk(&self, size: i32) -> Option<usize> {
         let mut inner = self.inner_exclusive_access();
-        let old_break = inner.program_brk;
-        let heap_bottom = inner.heap_bottom;
-        let new_break = inner.program_brk as isize + size as isize;
-        if new_break < inner.heap_bottom as isize {
+        if size > 0 && !inner.fits_address_space_limit(size as usize) {
+            return None;
+        }
+        let mut mm = inner.mm.lock();
+        let old_break = mm.program_brk;
+        let heap_bottom = mm.heap_bottom;
+        let new_break = mm.program_brk as isize + size as isize;
+        if new_break < mm.heap_bottom as isize {
             return None;
         }
 
         let result = if size < 0 {
-            inner
-                .memory_set
+            mm.memory_set
                 .shrink_to(VirtAddr(heap_bottom), VirtAddr(new_break as usize))
         } else {
-            inner
-                .memory_set
+            mm.memory_set
                 .append_to(VirtAddr(heap_bottom), VirtAddr(new_break as usize))
         };
 
         if result {
-            inner.program_brk = new_break as usize;
+            mm.program_brk = new_break as usize;
+            drop(mm);
+            inner.update_rss();
             Some(old_break)
         } else {
             None
         }
     }
 
-    pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
-        let mut parent_inner = self.inner_exclusive_access();
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
-        let trap_cx_ppn = memory_set
-            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+    /// Creates a child of `parent` as `clone` does, the caller has checked `flags`.
+    pub fn clone_task(
+        self: &Arc<TaskControlBlock>,
+        parent: &Arc<TaskControlBlock>,
+        flags: CloneFlags,
+        stack: usize,
+        tls: usize,
+    ) -> Arc<TaskControlBlock> {
+        let parent_mm = self.inner_exclusive_access().mm.clone();
+        let (mm, trap_cx_va) = if flags.contains(CloneFlags::VM) {
+            let trap_cx_va = map_trap_cx(&mut parent_mm.lock().memory_set);
+            (parent_mm, trap_cx_va)
+        } else {
+            let parent_space = parent_mm.lock().fork();
+            let trap_cx_va = self.inner_exclusive_access().trap_cx_va;
+            (Arc::new(SpinLock::new(parent_space)), trap_cx_va)
+        };
+        let trap_cx_ppn = mm
+            .lock()
+            .memory_set
+            .translate(VirtAddr::from(trap_cx_va).into())
             .unwrap()
             .ppn();
 
+        let parent_inner = self.inner_exclusive_access();
         let pid_handle = pid_alloc();
         let kernel_stack = kstack_alloc();
         let kernel_stack_top = kernel_stack.get_top();
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    task_status: TaskStatus::Ready,
-                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-                    start_time: 0,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: vec::Vec::new(),
-                    trap_cx_ppn,
-                    base_size: parent_inner.base_size,
-                    program_brk: parent_inner.program_brk,
-                    exit_code: 0,
-                    stride: 0,
-                    priority: 16,
-                    heap_bottom: parent_inner.heap_bottom,
-                    syscall_times: [0; MAX_SYSCALL_NUM],
-                })
-            },
+            on_cpu: AtomicBool::new(false),
+            inner: SpinLock::new(TaskControlBlockInner {
+                task_status: TaskStatus::Ready,
+                task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                start_time: 0,
+                mm,
+                sync: if flags.contains(CloneFlags::VM) {
+                    parent_inner.sync.clone()
+                } else {
+                    SyncTable::new()
+                },
+                fd_table: if flags.contains(CloneFlags::FILES) {
+                    parent_inner.fd_table.clone()
+                } else {
+                    parent_inner.fd_table.lock().fork()
+                },
+                parent: Some(Arc::downgrade(parent)),
+                children: vec::Vec::new(),
+                trap_cx_ppn,
+                trap_cx_va,
+                base_size: parent_inner.base_size,
+                exit_code: 0,
+                sched: SchedEntity::new(),
+                pi_held: vec::Vec::new(),
+                pi_blocked_on: None,
+                usage: ResourceUsage::new(),
+                children_usage: ResourceUsage::new(),
+                syscall_times: [0; MAX_SYSCALL_NUM],
+                affinity: parent_inner.affinity,
+                rlimits: parent_inner.rlimits,
+                kthread_entry: None,
+                is_kthread: false,
+                vfork_parent: None,
+                pgid: parent_inner.pgid,
+                sid: parent_inner.sid,
+                cred: parent_inner.cred,
+                name: parent_inner.name.clone(),
+                filters: parent_inner.filters.clone(),
+                ptrace: None,
+                signals: SignalSet::default(),
+                stop_signal: None,
+                stop_reported: false,
+                sleeper: None,
+                last_scheduled: 0,
+            }),
         });
-
-        parent_inner.children.push(task_control_block.clone());
-        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        // The child starts out with the floating-point registers the task has right now.
+        let parent_trap_cx = parent_inner.get_trap_cx();
+        save_fp_state(parent_trap_cx);
+        let parent_trap_cx = *parent_trap_cx;
+        drop(parent_inner);
+
+        let mut child_inner = task_control_block.inner_exclusive_access();
+        child_inner.update_rss();
+        let trap_cx = child_inner.get_trap_cx();
+        *trap_cx = parent_trap_cx;
+        forget_fp_owner(trap_cx);
         trap_cx.kernel_sp = kernel_stack_top;
+        // The child sees `clone` return 0.
+        trap_cx.x[10] = 0;
+        if stack != 0 {
+            trap_cx.set_sp(stack);
+        }
+        if flags.contains(CloneFlags::SETTLS) {
+            trap_cx.x[4] = tls;
+        }
+        drop(child_inner);
+
+        parent
+            .inner_exclusive_access()
+            .children
+            .push(task_control_block.clone());
         task_control_block
     }
 
-    pub fn exec(&self, elf_data: &[u8]) {
+    /// Wakes up the task that created this one with `CLONE_VFORK`, once this one no longer uses
+    /// its address space.
+    pub fn release_vfork_parent(&self) {
+        let vfork_parent = self.inner_exclusive_access().vfork_parent.take();
+        if let Some(parent) = vfork_parent {
+            wakeup_task(parent);
+        }
+    }
+
+    /// Replaces the program of the task, returns false and keeps the old one if the new image
+    /// does not fit the resource limits of the task.
+    pub fn exec(&self, name: &str, elf_data: &[u8]) -> bool {
         let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        if !image_fits_limits(&memory_set, &self.inner_exclusive_access().rlimits) {
+            return false;
+        }
 
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
@@ -154,24 +329,49 @@ impl TaskControlBlock {
             .ppn();
 
         let mut inner = self.inner_exclusive_access();
-        inner.memory_set = memory_set;
+        let old_mm = core::mem::replace(&mut inner.mm, UserSpace::new(memory_set, user_sp));
+        let old_trap_cx_va = core::mem::replace(&mut inner.trap_cx_va, TRAP_CONTEXT);
+        inner.sync = SyncTable::new();
         inner.trap_cx_ppn = trap_cx_ppn;
+        inner.name = String::from(name);
+        inner.update_rss();
 
         let trap_cx = inner.get_trap_cx();
 
         *trap_cx = TrapContext::app_init_context(
             entry_point,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             self.kernel_stack.get_top(),
             trap_handler as usize,
         );
-    }
+        drop(inner);
 
-    pub fn spawn(self: &Arc<TaskControlBlock>, elf_data: &[u8]) -> Arc<TaskControlBlock> {
-        let mut parent_inner = self.inner_exclusive_access();
+        // Other tasks may keep using the old address space, without the trap context of this one.
+        if Arc::strong_count(&old_mm) > 1 {
+            old_mm
+                .lock()
+                .memory_set
+                .remove_area_with_start_vpn(VirtAddr::from(old_trap_cx_va).into());
+        }
+        // Freed with interrupts enabled.
+        drop(old_mm);
+        self.release_vfork_parent();
+        true
+    }
 
+    /// Creates a child running a new program, `None` if the image does not fit the resource
+    /// limits the child would inherit.
+    pub fn spawn(
+        self: &Arc<TaskControlBlock>,
+        name: &str,
+        elf_data: &[u8],
+    ) -> Option<Arc<TaskControlBlock>> {
         let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let mut parent_inner = self.inner_exclusive_access();
+        if !image_fits_limits(&memory_set, &parent_inner.rlimits) {
+            return None;
+        }
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
@@ -184,43 +384,66 @@ impl TaskControlBlock {
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    task_status: TaskStatus::Ready,
-                    task_cx,
-                    start_time: 0,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: vec::Vec::new(),
-                    trap_cx_ppn,
-                    base_size: user_sp,
-                    program_brk: parent_inner.program_brk,
-                    stride: 0,
-                    priority: 16,
-                    exit_code: 0,
-                    heap_bottom: parent_inner.heap_bottom,
-                    syscall_times: [0; MAX_SYSCALL_NUM],
-                })
-            },
+            on_cpu: AtomicBool::new(false),
+            inner: SpinLock::new(TaskControlBlockInner {
+                task_status: TaskStatus::Ready,
+                task_cx,
+                start_time: 0,
+                mm: UserSpace::new(memory_set, user_sp),
+                sync: SyncTable::new(),
+                fd_table: parent_inner.fd_table.lock().fork(),
+                parent: Some(Arc::downgrade(self)),
+                children: vec::Vec::new(),
+                trap_cx_ppn,
+                trap_cx_va: TRAP_CONTEXT,
+                base_size: user_sp,
+                sched: SchedEntity::new(),
+                pi_held: vec::Vec::new(),
+                pi_blocked_on: None,
+                usage: ResourceUsage::new(),
+                children_usage: ResourceUsage::new(),
+                exit_code: 0,
+                syscall_times: [0; MAX_SYSCALL_NUM],
+                affinity: parent_inner.affinity,
+                rlimits: parent_inner.rlimits,
+                kthread_entry: None,
+                is_kthread: false,
+                vfork_parent: None,
+                pgid: parent_inner.pgid,
+                sid: parent_inner.sid,
+                cred: parent_inner.cred,
+                name: String::from(name),
+                filters: parent_inner.filters.clone(),
+                ptrace: None,
+                signals: SignalSet::default(),
+                stop_signal: None,
+                stop_reported: false,
+                sleeper: None,
+                last_scheduled: 0,
+            }),
         });
         parent_inner.children.push(task_control_block.clone());
+        task_control_block.inner_exclusive_access().update_rss();
 
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
 
         *trap_cx = TrapContext::app_init_context(
             entry_point,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             kernel_stack_top,
             trap_handler as usize,
         );
 
-        task_control_block
+        Some(task_control_block)
     }
 
     pub fn record_syscall_times(&self, syscall_id: usize) {
         let mut parent_inner = self.inner_exclusive_access();
-        parent_inner.syscall_times[syscall_id] += 1;
+        // Syscalls numbered past the table, like the synchronization ones, are not counted.
+        if let Some(times) = parent_inner.syscall_times.get_mut(syscall_id) {
+            *times += 1;
+        }
     }
 
     pub fn get_taskinfo(&self) -> TaskInfo {
@@ -229,6 +452,7 @@ impl TaskControlBlock {
             status: inner.task_status,
             syscall_times: inner.syscall_times,
             time: (get_time_us() - inner.start_time) / 1000,
+            deadline_misses: inner.sched.deadline_misses,
         }
     }
 }
@@ -237,22 +461,64 @@ pub struct TaskControlBlockInner {
     pub task_status: TaskStatus,
     pub task_cx: TaskContext,
     pub start_time: usize,
-    pub memory_set: MemorySet,
+    /// Address space, shared with the tasks created by or creating this one with `CLONE_VM`.
+    pub mm: Arc<SpinLock<UserSpace>>,
+    /// Mutexes, semaphores and condition variables, shared along with the address space.
+    pub sync: Arc<SpinLock<SyncTable>>,
+    /// Open files, shared with the tasks created by or creating this one with `CLONE_FILES`.
+    pub fd_table: Arc<SpinLock<FdTable>>,
     pub parent: Option<Weak<TaskControlBlock>>,
     pub children: vec::Vec<Arc<TaskControlBlock>>,
     pub trap_cx_ppn: PhysPageNum,
+    /// Where the trap context is mapped in the address space, tasks sharing one each have theirs.
+    pub trap_cx_va: usize,
     pub base_size: usize,
-    pub program_brk: usize,
     pub exit_code: i32,
-    pub heap_bottom: usize,
-    pub stride: u8,
-    pub priority: u8,
+    pub sched: SchedEntity,
+    /// Blocking mutexes the task holds.
+    pub pi_held: vec::Vec<Arc<SpinLock<MutexState>>>,
+    /// Blocking mutex the task waits for.
+    pub pi_blocked_on: Option<Arc<SpinLock<MutexState>>>,
     pub syscall_times: [u32; MAX_SYSCALL_NUM],
+    /// Harts the task may run on, one bit per hart.
+    pub affinity: usize,
+    pub usage: ResourceUsage,
+    pub rlimits: ResourceLimits,
+    /// Code a kernel thread runs, taken when it first gets a hart.
+    pub kthread_entry: Option<KernelThreadEntry>,
+    /// Set for kernel threads, which have no trap context.
+    pub is_kthread: bool,
+    /// Summed usage of the children reaped by `waitpid`, and of their reaped children.
+    pub children_usage: ResourceUsage,
+    /// Task suspended in `clone` with `CLONE_VFORK` until this one calls `exec` or exits.
+    pub vfork_parent: Option<Arc<TaskControlBlock>>,
+    /// Process group, for job control.
+    pub pgid: usize,
+    /// Session, the pid of its leader.
+    pub sid: usize,
+    /// Signals sent to the task it has not acted on yet.
+    pub signals: SignalSet,
+    /// Signal the task stopped by, `None` while it is not stopped.
+    pub stop_signal: Option<usize>,
+    /// Whether `waitpid` reported the current stop to the parent already.
+    pub stop_reported: bool,
+    /// Interruptible sleep the task is in, ended by signals that terminate or stop it.
+    pub sleeper: Option<Arc<Sleeper>>,
+    /// Last time the task was switched to or away from, for the watchdog.
+    pub last_scheduled: usize,
+    /// User and group ids the task acts with.
+    pub cred: Credentials,
+    /// Name of the program the task runs, for diagnostics.
+    pub name: String,
+    /// Syscall filters installed with `seccomp`.
+    pub filters: SyscallFilters,
+    /// Set while the parent traces the task, not inherited by children.
+    pub ptrace: Option<TraceState>,
 }
 
 impl TaskControlBlockInner {
     pub fn get_user_token(&self) -> usize {
-        self.memory_set.token()
+        self.mm.lock().memory_set.token()
     }
 
     pub fn get_trap_cx(&self) -> &'static mut TrapContext {
@@ -260,11 +526,28 @@ impl TaskControlBlockInner {
     }
 
     pub fn mmap(&mut self, start: usize, len: usize, port: usize) -> bool {
-        self.memory_set.mmap(start, len, port)
+        if !self.fits_address_space_limit(len) {
+            return false;
+        }
+        let mapped = self.mm.lock().memory_set.mmap(start, len, port);
+        self.update_rss();
+        mapped
     }
 
     pub fn munmap(&mut self, start: usize, len: usize) -> bool {
-        self.memory_set.munmap(start, len)
+        self.mm.lock().memory_set.munmap(start, len)
+    }
+
+    /// Whether the address space may grow by `bytes` under `RLIMIT_AS`.
+    pub fn fits_address_space_limit(&self, bytes: usize) -> bool {
+        (self.mm.lock().memory_set.mapped_pages() * PAGE_SIZE).saturating_add(bytes)
+            <= self.rlimits.cur(RLIMIT_AS)
+    }
+
+    /// Records the current size of the address space in the peak resident set.
+    pub fn update_rss(&mut self) {
+        let pages = self.mm.lock().memory_set.resident_pages();
+        self.usage.update_rss(pages);
     }
 
     pub fn is_zombie(&self) -> bool {
@@ -272,10 +555,61 @@ impl TaskControlBlockInner {
     }
 
     pub fn set_priority(&mut self, priority: isize) {
-        self.priority = priority as u8;
+        self.sched.set_priority(priority as u8);
     }
 }
 
+/// User address space of a task along with its heap.
+pub struct UserSpace {
+    pub memory_set: MemorySet,
+    pub heap_bottom: usize,
+    pub program_brk: usize,
+}
+
+impl UserSpace {
+    /// Address space of a freshly loaded program, its heap starts empty at `heap_bottom`.
+    pub fn new(memory_set: MemorySet, heap_bottom: usize) -> Arc<SpinLock<Self>> {
+        Arc::new(SpinLock::new(Self {
+            memory_set,
+            heap_bottom,
+            program_brk: heap_bottom,
+        }))
+    }
+
+    /// A copy of the address space with the contents of all pages.
+    pub fn fork(&self) -> Self {
+        Self {
+            memory_set: MemorySet::from_existed_user(&self.memory_set),
+            heap_bottom: self.heap_bottom,
+            program_brk: self.program_brk,
+        }
+    }
+}
+
+/// Maps a trap context for one more task sharing `memory_set`, in the first free page below
+/// `TRAP_CONTEXT`, and returns its address.
+fn map_trap_cx(memory_set: &mut MemorySet) -> usize {
+    let mut trap_cx_va = TRAP_CONTEXT;
+    while memory_set
+        .translate(VirtAddr::from(trap_cx_va).into())
+        .is_some_and(|pte| pte.is_valid())
+    {
+        trap_cx_va -= PAGE_SIZE;
+    }
+    memory_set.insert_framed_area(
+        trap_cx_va.into(),
+        (trap_cx_va + PAGE_SIZE).into(),
+        MapPermission::R | MapPermission::W,
+    );
+    trap_cx_va
+}
+
+/// Whether a freshly loaded program stays within `RLIMIT_AS` and its stack within `RLIMIT_STACK`.
+fn image_fits_limits(memory_set: &MemorySet, rlimits: &ResourceLimits) -> bool {
+    USER_STACK_SIZE <= rlimits.cur(RLIMIT_STACK)
+        && memory_set.mapped_pages() * PAGE_SIZE <= rlimits.cur(RLIMIT_AS)
+}
+
 #[allow(unused)]
 #[derive(Clone, Copy, PartialEq)]
 pub enum TaskStatus {
@@ -283,4 +617,10 @@ pub enum TaskStatus {
     Ready,
     Running,
     Zombie,
+    /// Waiting for an event, off all ready queues until `wakeup_task`.
+    Blocked,
+    /// Stopped by a signal until a `SIGCONT`.
+    Stopped,
+    /// Stopped for its tracer until that resumes it, see `ptrace`.
+    Traced,
 }
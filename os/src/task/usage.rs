@@ -0,0 +1,100 @@
+/// Resources consumed by a task, times in microseconds.
+#[derive(Clone, Copy, Default)]
+pub struct ResourceUsage {
+    /// Time spent running in user mode.
+    pub utime: usize,
+    /// Time spent in the kernel on behalf of the task.
+    pub stime: usize,
+    /// Times the task gave up the hart itself, by yielding or blocking.
+    pub nvcsw: usize,
+    /// Times the task was preempted.
+    pub nivcsw: usize,
+    /// Page faults the task took, all of them fatal as no page is mapped on demand.
+    pub page_faults: usize,
+    /// Largest number of pages the address space ever had mapped.
+    pub max_rss_pages: usize,
+    /// Start of the interval not yet charged to `utime` or `stime`.
+    stamp: usize,
+}
+
+impl ResourceUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Charges the time since the last stamp to user mode, at trap entry.
+    pub fn charge_user(&mut self, now: usize) {
+        self.utime += now.saturating_sub(self.stamp);
+        self.stamp = now;
+    }
+
+    /// Charges the time since the last stamp to the kernel, on the way back to user mode.
+    pub fn charge_kernel(&mut self, now: usize) {
+        self.stime += now.saturating_sub(self.stamp);
+        self.stamp = now;
+    }
+
+    pub fn switch_in(&mut self, now: usize) {
+        self.stamp = now;
+    }
+
+    pub fn switch_out(&mut self, now: usize, voluntary: bool) {
+        self.charge_kernel(now);
+        if voluntary {
+            self.nvcsw += 1;
+        } else {
+            self.nivcsw += 1;
+        }
+    }
+
+    pub fn update_rss(&mut self, pages: usize) {
+        self.max_rss_pages = self.max_rss_pages.max(pages);
+    }
+
+    /// Adds the usage of a reaped child, the peak resident set is the largest of any child.
+    pub fn accumulate(&mut self, child: &ResourceUsage) {
+        self.utime += child.utime;
+        self.stime += child.stime;
+        self.nvcsw += child.nvcsw;
+        self.nivcsw += child.nivcsw;
+        self.page_faults += child.page_faults;
+        self.max_rss_pages = self.max_rss_pages.max(child.max_rss_pages);
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn usage_test() {
+    use log::info;
+
+    use super::{current_task, suspend_current_and_run_next};
+
+    let mut usage = ResourceUsage::new();
+    usage.switch_in(100);
+    usage.charge_user(130);
+    usage.charge_kernel(150);
+    usage.switch_out(160, true);
+    usage.switch_in(200);
+    usage.switch_out(210, false);
+    usage.update_rss(8);
+    usage.update_rss(4);
+    assert_eq!((usage.utime, usage.stime), (30, 40));
+    assert_eq!((usage.nvcsw, usage.nivcsw, usage.max_rss_pages), (1, 1, 8));
+
+    let mut parent = ResourceUsage::new();
+    parent.max_rss_pages = 16;
+    parent.accumulate(&usage);
+    parent.accumulate(&usage);
+    assert_eq!(
+        (parent.utime, parent.nvcsw, parent.max_rss_pages),
+        (60, 2, 16)
+    );
+
+    let task = current_task().unwrap();
+    let before = task.inner_exclusive_access().usage;
+    suspend_current_and_run_next();
+    let after = task.inner_exclusive_access().usage;
+    assert_eq!(after.nvcsw, before.nvcsw + 1);
+    assert!(after.stime >= before.stime);
+
+    info!("usage_test passed !");
+}
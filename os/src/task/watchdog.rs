@@ -0,0 +1,229 @@
+use core::arch::asm;
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use log::warn;
+
+use super::manager::all_tasks;
+use super::processor::current_task;
+use super::task::TaskStatus;
+use super::workqueue::schedule_work;
+use crate::config::{HUNG_TASK_TIMEOUT_US, KERNEL_STACK_SIZE, LOCKUP_TIMEOUT_US, MAX_HARTS};
+use crate::smp::{hart_id, online_harts};
+use crate::timer::get_time_us;
+
+/// How often the harts and the blocked tasks are checked.
+const CHECK_PERIOD_US: usize = 1_000_000;
+/// Frames a kernel backtrace follows at most.
+const MAX_BACKTRACE_FRAMES: usize = 32;
+
+/// Whether the watchdog panics after a report, set by the `watchdog_panic` feature.
+static WATCHDOG_PANIC: AtomicBool = AtomicBool::new(cfg!(feature = "watchdog_panic"));
+
+/// What the watchdog knows about a hart, without taking any lock a stuck hart may hold.
+struct HartWatch {
+    /// Last time the hart scheduled or took a tick it could have preempted at.
+    progress_us: AtomicUsize,
+    /// Last time the hart took a timer interrupt, whether it made progress or not.
+    tick_us: AtomicUsize,
+    /// Task the hart switched to last, 0 while it idles.
+    pid: AtomicUsize,
+    /// Whether the hart was reported since its last progress.
+    reported: AtomicBool,
+}
+
+impl HartWatch {
+    const fn new() -> Self {
+        Self {
+            progress_us: AtomicUsize::new(0),
+            tick_us: AtomicUsize::new(0),
+            pid: AtomicUsize::new(0),
+            reported: AtomicBool::new(false),
+        }
+    }
+}
+
+static HARTS: [HartWatch; MAX_HARTS] = [const { HartWatch::new() }; MAX_HARTS];
+
+/// When the next check is due.
+static NEXT_CHECK_US: AtomicUsize = AtomicUsize::new(0);
+/// When blocked tasks were last checked.
+static LAST_HUNG_CHECK_US: AtomicUsize = AtomicUsize::new(0);
+
+/// Records that this hart makes progress.
+pub fn touch_watchdog() {
+    let watch = &HARTS[hart_id()];
+    watch.progress_us.store(get_time_us(), Ordering::Relaxed);
+    watch.reported.store(false, Ordering::Relaxed);
+}
+
+/// Records the task this hart switches to, 0 when it goes back to idle.
+pub fn watchdog_switch(pid: usize) {
+    HARTS[hart_id()].pid.store(pid, Ordering::Relaxed);
+}
+
+/// Logs the kernel call chain from `ra` and the frame pointer `fp`, as long as it stays in `stack`.
+fn log_kernel_backtrace(ra: usize, mut fp: usize, stack: Range<usize>) {
+    warn!("[kernel]   #0 {:#x}", ra);
+    for depth in 1..MAX_BACKTRACE_FRAMES {
+        if fp < stack.start + 16 || fp > stack.end {
+            break;
+        }
+        let (ra, prev_fp) = unsafe { (*(fp as *const usize).sub(1), *(fp as *const usize).sub(2)) };
+        if ra == 0 {
+            break;
+        }
+        warn!("[kernel]   #{} {:#x}", depth, ra);
+        if prev_fp <= fp {
+            break;
+        }
+        fp = prev_fp;
+    }
+}
+
+fn kernel_stack_range(top: usize) -> Range<usize> {
+    top - KERNEL_STACK_SIZE..top
+}
+
+fn give_up(what: &str) {
+    if WATCHDOG_PANIC.load(Ordering::Relaxed) {
+        panic!("watchdog: {}", what);
+    }
+}
+
+/// Reports this hart, which took a tick but made no progress for `stuck_us`.
+fn report_soft_lockup(stuck_us: usize) {
+    warn!(
+        "[kernel] watchdog: soft lockup on hart {}, stuck for {} ms",
+        hart_id(),
+        stuck_us / 1000
+    );
+    if let Some(task) = current_task() {
+        let name = task.inner_exclusive_access().name.clone();
+        warn!("[kernel]   running pid {} ({})", task.getpid(), name);
+        let (ra, fp): (usize, usize);
+        unsafe {
+            asm!("auipc {}, 0", "mv {}, fp", out(reg) ra, out(reg) fp);
+        }
+        log_kernel_backtrace(ra, fp, kernel_stack_range(task.kernel_stack.get_top()));
+    }
+    give_up("soft lockup");
+}
+
+/// Reports another hart that made no progress for `stuck_us`, not even taking a tick.
+fn report_hard_lockup(hart: usize, stuck_us: usize) {
+    warn!(
+        "[kernel] watchdog: hard lockup on hart {}, stuck for {} ms with interrupts disabled, \
+        running pid {}",
+        hart,
+        stuck_us / 1000,
+        HARTS[hart].pid.load(Ordering::Relaxed)
+    );
+    give_up("hard lockup");
+}
+
+/// Reports the harts that made no progress for `LOCKUP_TIMEOUT_US`, each once until it does.
+fn check_lockups(now: usize) {
+    let this_hart = hart_id();
+    let online = online_harts();
+    for (hart, watch) in HARTS.iter().enumerate() {
+        if online & (1 << hart) == 0 {
+            continue;
+        }
+        let mut last_seen = watch.progress_us.load(Ordering::Relaxed);
+        if hart != this_hart {
+            last_seen = last_seen.max(watch.tick_us.load(Ordering::Relaxed));
+        }
+        let stuck_us = now.saturating_sub(last_seen);
+        if stuck_us < LOCKUP_TIMEOUT_US || watch.reported.swap(true, Ordering::Relaxed) {
+            continue;
+        }
+        if hart == this_hart {
+            report_soft_lockup(stuck_us);
+        } else {
+            report_hard_lockup(hart, stuck_us);
+        }
+    }
+}
+
+/// Reports the user tasks in an uninterruptible sleep for more than `HUNG_TASK_TIMEOUT_US`, once.
+fn check_hung_tasks() {
+    let now = get_time_us();
+    let last_check = LAST_HUNG_CHECK_US.swap(now, Ordering::Relaxed);
+    let mut hung = false;
+    for task in all_tasks() {
+        let inner = task.inner_exclusive_access();
+        if inner.task_status != TaskStatus::Blocked || inner.is_kthread || inner.sleeper.is_some() {
+            continue;
+        }
+        let blocked_us = now.saturating_sub(inner.last_scheduled);
+        if blocked_us < HUNG_TASK_TIMEOUT_US
+            || last_check.saturating_sub(inner.last_scheduled) >= HUNG_TASK_TIMEOUT_US
+        {
+            continue;
+        }
+        warn!(
+            "[kernel] watchdog: pid {} ({}) blocked for {} s",
+            task.getpid(),
+            inner.name,
+            blocked_us / 1_000_000
+        );
+        let (ra, fp) = inner.task_cx.backtrace_start();
+        let stack = kernel_stack_range(task.kernel_stack.get_top());
+        drop(inner);
+        log_kernel_backtrace(ra, fp, stack);
+        hung = true;
+    }
+    if hung {
+        give_up("hung task");
+    }
+}
+
+/// Runs on timer interrupts of all harts, the first one to find a check due carries it out.
+pub fn watchdog_tick() {
+    let now = get_time_us();
+    HARTS[hart_id()].tick_us.store(now, Ordering::Relaxed);
+    let due = NEXT_CHECK_US.load(Ordering::Relaxed);
+    if now < due
+        || NEXT_CHECK_US
+            .compare_exchange(
+                due,
+                now + CHECK_PERIOD_US,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_err()
+    {
+        return;
+    }
+    check_lockups(now);
+    schedule_work(check_hung_tasks);
+}
+
+#[cfg(feature = "selftest")]
+pub fn watchdog_test() {
+    use log::info;
+
+    use crate::sync::{disable_interrupts, restore_interrupts};
+
+    let irq = disable_interrupts();
+    let watch = &HARTS[hart_id()];
+    watchdog_switch(42);
+    assert_eq!(watch.pid.load(Ordering::Relaxed), 42);
+    watchdog_switch(0);
+
+    touch_watchdog();
+    let now = watch.progress_us.load(Ordering::Relaxed);
+    check_lockups(now);
+    assert!(!watch.reported.load(Ordering::Relaxed));
+    // Pretending the timeout passed reports every hart, this one only once until it progresses.
+    let panic = WATCHDOG_PANIC.swap(false, Ordering::Relaxed);
+    check_lockups(now + LOCKUP_TIMEOUT_US);
+    assert!(watch.reported.load(Ordering::Relaxed));
+    touch_watchdog();
+    assert!(!watch.reported.load(Ordering::Relaxed));
+    WATCHDOG_PANIC.store(panic, Ordering::Relaxed);
+    restore_interrupts(irq);
+
+    info!("watchdog_test passed !");
+}
@@ -0,0 +1,115 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use lazy_static::lazy_static;
+
+use super::block_current_and_run_next;
+use super::kthread::spawn_kernel_thread;
+use super::manager::wakeup_task;
+use super::processor::current_task;
+use super::task::TaskControlBlock;
+use crate::sync::{preempt_disable, preempt_enable, SpinLock};
+
+type Work = Box<dyn FnOnce() + Send>;
+
+/// Work deferred to kernel worker threads, in the order it was queued.
+pub struct WorkQueue {
+    inner: SpinLock<WorkQueueInner>,
+}
+
+struct WorkQueueInner {
+    works: VecDeque<Work>,
+    /// Workers blocked because there was nothing to do.
+    idle_workers: Vec<Arc<TaskControlBlock>>,
+}
+
+impl WorkQueue {
+    pub fn new() -> Self {
+        Self {
+            inner: SpinLock::new(WorkQueueInner {
+                works: VecDeque::new(),
+                idle_workers: Vec::new(),
+            }),
+        }
+    }
+
+    /// Queues `work` and wakes up an idle worker. Never blocks, interrupt handlers may use it.
+    pub fn queue_work<F>(&self, work: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut inner = self.inner.lock();
+        inner.works.push_back(Box::new(work));
+        let worker = inner.idle_workers.pop();
+        drop(inner);
+
+        if let Some(worker) = worker {
+            wakeup_task(worker);
+        }
+    }
+
+    fn worker_loop(&self) -> ! {
+        loop {
+            let mut inner = self.inner.lock();
+            match inner.works.pop_front() {
+                Some(work) => {
+                    drop(inner);
+                    work();
+                }
+                None => {
+                    // Registered under the lock, so work queued from now on wakes this worker.
+                    preempt_disable();
+                    inner.idle_workers.push(current_task().unwrap());
+                    drop(inner);
+                    block_current_and_run_next();
+                    preempt_enable();
+                }
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref SYSTEM_WQ: WorkQueue = WorkQueue::new();
+}
+
+/// Starts the worker thread of the system workqueue.
+pub fn init_workqueue() {
+    spawn_kernel_thread(|| SYSTEM_WQ.worker_loop());
+}
+
+/// Defers `work` to the worker of the system workqueue.
+pub fn schedule_work<F>(work: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    SYSTEM_WQ.queue_work(work);
+}
+
+#[cfg(feature = "selftest")]
+pub fn workqueue_test() {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use log::info;
+
+    use crate::selftest::wait_until;
+
+    // Work runs in the order it was queued, a kernel thread right away.
+    let done = Arc::new(SpinLock::new(Vec::new()));
+    for i in 0..4 {
+        let done = done.clone();
+        schedule_work(move || done.lock().push(i));
+    }
+    let ran = Arc::new(AtomicUsize::new(0));
+    let thread_ran = ran.clone();
+    spawn_kernel_thread(move || {
+        thread_ran.store(current_task().unwrap().getpid(), Ordering::SeqCst);
+    });
+    wait_until(|| done.lock().len() == 4 && ran.load(Ordering::SeqCst) != 0);
+    assert_eq!(*done.lock(), [0, 1, 2, 3]);
+    assert_ne!(ran.load(Ordering::SeqCst), current_task().unwrap().getpid());
+
+    info!("workqueue_test passed !");
+}
@@ -1,12 +1,21 @@
+use alloc::collections::BinaryHeap;
+use alloc::sync::{Arc, Weak};
+use core::cmp::Ordering;
+
+use lazy_static::lazy_static;
 use riscv::register::time;
 use sbi_rt::set_timer;
 
 use crate::config::CLOCK_FREQ;
+use crate::sync::{Sleeper, SpinLock, WakeReason};
+use crate::task::{wakeup_task, TaskControlBlock};
 
 const TICKS_PRE_SEC: usize = 100;
 const MICRO_PRE_SEC: usize = 1_000_000;
 const MESC_PRE_SEC: usize = 1000;
 
+pub const TICK_US: usize = MICRO_PRE_SEC / TICKS_PRE_SEC;
+
 pub fn get_time() -> usize {
     time::read()
 }
@@ -15,6 +24,11 @@ pub fn set_next_trigger() {
     set_timer((get_time() + CLOCK_FREQ / TICKS_PRE_SEC) as u64);
 }
 
+/// Programs the timer interrupt of this hart for the absolute time `us`.
+pub fn set_trigger_at_us(us: usize) {
+    set_timer((us * (CLOCK_FREQ / MICRO_PRE_SEC)) as u64);
+}
+
 pub fn get_time_us() -> usize {
     time::read() / (CLOCK_FREQ / MICRO_PRE_SEC)
 }
@@ -23,3 +37,101 @@ pub fn get_time_us() -> usize {
 pub fn get_time_ms() -> usize {
     time::read() / (CLOCK_FREQ / MESC_PRE_SEC)
 }
+
+/// What a timer wakes up.
+pub enum TimerTarget {
+    Task(Arc<TaskControlBlock>),
+    /// A sleep with a timeout, not kept alive by the timer.
+    Sleep(Weak<Sleeper>),
+}
+
+/// A blocked task to wake up at `expire_us`.
+pub struct TimerCondVar {
+    pub expire_us: usize,
+    pub target: TimerTarget,
+}
+
+impl PartialEq for TimerCondVar {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_us == other.expire_us
+    }
+}
+
+impl Eq for TimerCondVar {}
+
+impl PartialOrd for TimerCondVar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerCondVar {
+    // Reversed, so the heap pops the earliest timer first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire_us.cmp(&self.expire_us)
+    }
+}
+
+lazy_static! {
+    static ref TIMERS: SpinLock<BinaryHeap<TimerCondVar>> = SpinLock::new(BinaryHeap::new());
+}
+
+pub fn add_timer(expire_us: usize, task: Arc<TaskControlBlock>) {
+    TIMERS.lock().push(TimerCondVar {
+        expire_us,
+        target: TimerTarget::Task(task),
+    });
+}
+
+/// Ends `sleeper` at `expire_us` with `WakeReason::TimedOut`, unless it ended by then.
+pub fn add_timeout(expire_us: usize, sleeper: &Arc<Sleeper>) {
+    TIMERS.lock().push(TimerCondVar {
+        expire_us,
+        target: TimerTarget::Sleep(Arc::downgrade(sleeper)),
+    });
+}
+
+pub fn next_timer_expiry() -> Option<usize> {
+    TIMERS.lock().peek().map(|timer| timer.expire_us)
+}
+
+/// Wakes up the tasks whose timers expired.
+pub fn check_timer() {
+    let now = get_time_us();
+    let mut timers = TIMERS.lock();
+    while let Some(timer) = timers.peek() {
+        if timer.expire_us > now {
+            break;
+        }
+        match timers.pop().unwrap().target {
+            TimerTarget::Task(task) => wakeup_task(task),
+            TimerTarget::Sleep(sleeper) => {
+                if let Some(sleeper) = sleeper.upgrade() {
+                    sleeper.wake(WakeReason::TimedOut);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "selftest")]
+pub fn timer_test() {
+    use log::info;
+
+    use crate::sync::{preempt_disable, preempt_enable};
+    use crate::task::{block_current_and_run_next, current_task};
+
+    // Idle harts stop ticking but still wake the task up on time.
+    for delay in [TICK_US / 2, 5 * TICK_US] {
+        let expire = get_time_us() + delay;
+        preempt_disable();
+        add_timer(expire, current_task().unwrap());
+        assert!(next_timer_expiry().is_some_and(|next| next <= expire));
+        block_current_and_run_next();
+        preempt_enable();
+        let now = get_time_us();
+        assert!(now >= expire && now < expire + 10 * TICK_US);
+    }
+
+    info!("timer_test passed !");
+}
@@ -1,6 +1,9 @@
-use riscv::register::sstatus;
+use riscv::register::sstatus::{self, FS};
+
+use super::fp::FpContext;
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct TrapContext {
     pub x: [usize; 32],
     pub sstatus: sstatus::Sstatus,
@@ -8,6 +11,10 @@ pub struct TrapContext {
     pub kernel_satp: usize,
     pub kernel_sp: usize,
     pub trap_handler: usize,
+    /// Id of the hart the task runs on, loaded into `tp` when trapping into the kernel.
+    pub kernel_tp: usize,
+    /// Floating-point state, only saved and loaded as needed, see `save_fp_state`.
+    pub fp: FpContext,
 }
 
 impl TrapContext {
@@ -24,6 +31,8 @@ impl TrapContext {
     ) -> Self {
         let mut sstatus = sstatus::read();
         sstatus.set_spp(sstatus::SPP::User);
+        // Whatever the kernel runs with, the FPU stays off until the program uses it.
+        sstatus.set_fs(FS::Off);
 
         let mut cx = Self {
             x: [0; 32],
@@ -32,6 +41,8 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            kernel_tp: 0,
+            fp: FpContext::new(),
         };
 
         cx.set_sp(sp);
@@ -0,0 +1,180 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use log::error;
+use riscv::register::scause::{Exception, Scause, Trap};
+
+use super::{TrapContext, LOAD_MISALIGNED};
+use crate::mm::{MapPermission, MemorySet, VirtAddr};
+use crate::task::current_task;
+
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// Frames a backtrace follows at most.
+const MAX_BACKTRACE_FRAMES: usize = 32;
+/// Areas listed on each side of the faulting address.
+const AREAS_AROUND_FAULT: usize = 2;
+
+struct Permission(MapPermission);
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (perm, c) in [
+            (MapPermission::R, 'r'),
+            (MapPermission::W, 'w'),
+            (MapPermission::X, 'x'),
+            (MapPermission::U, 'u'),
+        ] {
+            write!(f, "{}", if self.0.contains(perm) { c } else { '-' })?;
+        }
+        Ok(())
+    }
+}
+
+/// Logs the return addresses found by following the frame pointer `s0` up the user stack.
+fn log_backtrace(memory_set: &MemorySet, cx: &TrapContext) {
+    let read = |va: usize| memory_set.user_word(VirtAddr::from(va)).map(|word| *word);
+
+    error!("[kernel] backtrace:");
+    error!("[kernel]   #0 {:#x}", cx.sepc);
+    let mut fp = cx.x[8];
+    for depth in 1..MAX_BACKTRACE_FRAMES {
+        let ra = fp.checked_sub(8).and_then(read);
+        let prev_fp = fp.checked_sub(16).and_then(read);
+        let (Some(ra), Some(prev_fp)) = (ra, prev_fp) else {
+            break;
+        };
+        if ra == 0 {
+            break;
+        }
+        error!("[kernel]   #{} {:#x}", depth, ra);
+        // Callers' frames lie further up the stack, anything else is not a frame.
+        if prev_fp <= fp {
+            break;
+        }
+        fp = prev_fp;
+    }
+}
+
+/// Logs the user areas next to `addr`, marking where it falls.
+fn log_areas(memory_set: &MemorySet, addr: usize) {
+    let mut areas: Vec<_> = memory_set.user_areas().collect();
+    areas.sort_by_key(|area| area.start_va());
+    // The first area ending above the address, the one containing it if there is one.
+    let idx = areas.partition_point(|area| usize::from(area.end_va()) <= addr);
+    let from = idx.saturating_sub(AREAS_AROUND_FAULT);
+    let to = (idx + AREAS_AROUND_FAULT).min(areas.len());
+
+    error!("[kernel] memory map around {:#x}:", addr);
+    for (i, area) in areas.iter().enumerate().take(to).skip(from) {
+        let (start, end) = (usize::from(area.start_va()), usize::from(area.end_va()));
+        if i == idx && addr < start {
+            error!("[kernel]   {:#x} unmapped <--", addr);
+        }
+        let marker = if (start..end).contains(&addr) {
+            " <--"
+        } else {
+            ""
+        };
+        error!(
+            "[kernel]   {:#x}-{:#x} {}{}",
+            start,
+            end,
+            Permission(area.permission()),
+            marker
+        );
+    }
+    if idx == areas.len() {
+        error!("[kernel]   {:#x} unmapped <--", addr);
+    }
+}
+
+/// Address an exception is about: the one accessed for faults, that of the instruction otherwise.
+fn fault_addr(scause: Scause, stval: usize, sepc: usize) -> usize {
+    match scause.cause() {
+        Trap::Exception(
+            Exception::InstructionMisaligned
+            | Exception::InstructionFault
+            | Exception::InstructionPageFault
+            | Exception::LoadFault
+            | Exception::LoadPageFault
+            | Exception::StoreMisaligned
+            | Exception::StoreFault
+            | Exception::StorePageFault,
+        ) => stval,
+        Trap::Exception(Exception::Unknown) if scause.code() == LOAD_MISALIGNED => stval,
+        _ => sepc,
+    }
+}
+
+/// Logs a report on the running task, which took the exception in `scause` with `stval`.
+pub fn report_crash(scause: Scause, stval: usize) {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let cx = *inner.get_trap_cx();
+    let name = inner.name.clone();
+    let mm = inner.mm.clone();
+    drop(inner);
+
+    error!(
+        "[kernel] ---- crash of pid {} ({}) ----",
+        task.getpid(),
+        name
+    );
+    error!(
+        "[kernel] {:?} at sepc = {:#x}, stval = {:#x}",
+        scause.cause(),
+        cx.sepc,
+        stval
+    );
+    for i in (0..32).step_by(4) {
+        error!(
+            "[kernel] {:>4} = {:#018x} {:>4} = {:#018x} {:>4} = {:#018x} {:>4} = {:#018x}",
+            REG_NAMES[i],
+            cx.x[i],
+            REG_NAMES[i + 1],
+            cx.x[i + 1],
+            REG_NAMES[i + 2],
+            cx.x[i + 2],
+            REG_NAMES[i + 3],
+            cx.x[i + 3]
+        );
+    }
+    error!("[kernel] sstatus = {:#x}", cx.sstatus.bits());
+
+    let mm = mm.lock();
+    log_backtrace(&mm.memory_set, &cx);
+    log_areas(&mm.memory_set, fault_addr(scause, stval, cx.sepc));
+}
+
+#[cfg(feature = "selftest")]
+pub fn crash_test() {
+    use log::info;
+    use riscv::register::scause;
+
+    use crate::sync::{disable_interrupts, restore_interrupts};
+
+    let fault_addr_of = |code: usize| {
+        let irq = disable_interrupts();
+        unsafe {
+            scause::write(code);
+        }
+        let cause = scause::read();
+        restore_interrupts(irq);
+        fault_addr(cause, 0x1000, 0x2000)
+    };
+    // Load and store faults of all kinds are about the address accessed.
+    for code in [0, 1, 4, 5, 6, 7, 12, 13, 15] {
+        assert_eq!(fault_addr_of(code), 0x1000);
+    }
+    // Illegal instructions and breakpoints are about the instruction.
+    for code in [2, 3] {
+        assert_eq!(fault_addr_of(code), 0x2000);
+    }
+
+    info!("crash_test passed !");
+}
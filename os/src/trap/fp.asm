@@ -0,0 +1,30 @@
+.altmacro
+.macro SAVE_FP n
+    fsd f\n, \n*8(a0)
+.endm
+.macro LOAD_FP n
+    fld f\n, \n*8(a0)
+.endm
+    .section .text
+    .globl __save_fp
+    .globl __load_fp
+__save_fp:
+    # __save_fp(fp_cx_ptr: *mut FpContext)
+    .set n, 0
+    .rept 32
+        SAVE_FP %n
+        .set n, n + 1
+    .endr
+    frcsr t0
+    sd t0, 32*8(a0)
+    ret
+__load_fp:
+    # __load_fp(fp_cx_ptr: *const FpContext)
+    .set n, 0
+    .rept 32
+        LOAD_FP %n
+        .set n, n + 1
+    .endr
+    ld t0, 32*8(a0)
+    fscsr t0
+    ret
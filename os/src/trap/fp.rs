@@ -0,0 +1,161 @@
+use core::arch::global_asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use riscv::register::sstatus::{self, FS};
+
+use super::TrapContext;
+use crate::config::MAX_HARTS;
+use crate::smp::hart_id;
+
+global_asm!(include_str!("fp.asm"));
+
+extern "C" {
+    fn __save_fp(fp_cx_ptr: *mut FpContext);
+    fn __load_fp(fp_cx_ptr: *const FpContext);
+}
+
+/// `FpContext::hart` of a state not loaded into any hart's registers.
+const NO_HART: usize = usize::MAX;
+
+/// Trap context whose floating-point state each hart's registers hold, 0 for none.
+static FP_OWNERS: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(0) }; MAX_HARTS];
+
+/// Floating-point registers `f0` to `f31` and `fcsr` of a user task.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FpContext {
+    pub f: [u64; 32],
+    pub fcsr: usize,
+    /// Hart the state was last loaded on.
+    hart: usize,
+}
+
+impl FpContext {
+    /// All registers zero, not loaded anywhere.
+    pub const fn new() -> Self {
+        Self {
+            f: [0; 32],
+            fcsr: 0,
+            hart: NO_HART,
+        }
+    }
+}
+
+/// Runs `f` with the FPU of this hart enabled for the kernel.
+fn with_fpu<T>(f: impl FnOnce() -> T) -> T {
+    let fs = sstatus::read().fs();
+    unsafe {
+        sstatus::set_fs(FS::Clean);
+    }
+    let ret = f();
+    unsafe {
+        sstatus::set_fs(fs);
+    }
+    ret
+}
+
+/// Saves the floating-point registers into `cx` if the task wrote any of them since the last save.
+pub fn save_fp_state(cx: &mut TrapContext) {
+    if cx.sstatus.fs() != FS::Dirty {
+        return;
+    }
+    with_fpu(|| unsafe { __save_fp(&mut cx.fp) });
+    cx.sstatus.set_fs(FS::Clean);
+}
+
+/// Loads the floating-point state of `cx` unless it is still there. Interrupts must be disabled.
+pub fn restore_fp_state(cx: &mut TrapContext) {
+    if cx.sstatus.fs() == FS::Off {
+        return;
+    }
+    let hart = hart_id();
+    let owner = cx as *const TrapContext as usize;
+    if cx.fp.hart == hart && FP_OWNERS[hart].load(Ordering::Relaxed) == owner {
+        return;
+    }
+    with_fpu(|| unsafe { __load_fp(&cx.fp) });
+    cx.fp.hart = hart;
+    FP_OWNERS[hart].store(owner, Ordering::Relaxed);
+}
+
+/// Turns the FPU of a task on, returns false if it was on already.
+pub fn enable_fp(cx: &mut TrapContext) -> bool {
+    if cx.sstatus.fs() != FS::Off {
+        return false;
+    }
+    cx.fp = FpContext::new();
+    cx.sstatus.set_fs(FS::Initial);
+    true
+}
+
+/// Makes a copy of a trap context, as a cloned task gets, load its own floating-point state.
+pub fn forget_fp_owner(cx: &mut TrapContext) {
+    cx.fp.hart = NO_HART;
+}
+
+#[cfg(feature = "selftest")]
+pub fn fp_test() {
+    use alloc::sync::Arc;
+    use core::arch::asm;
+
+    use log::info;
+
+    use crate::selftest::wait_until;
+    use crate::sync::{disable_interrupts, preempt_disable, preempt_enable, restore_interrupts};
+    use crate::task::{
+        block_current_and_run_next, current_task, spawn_kernel_thread, suspend_current_and_run_next,
+    };
+    use crate::timer::{add_timer, get_time_us, TICK_US};
+
+    // Kernel threads have no floating-point state to save, also once they started.
+    let done = Arc::new(AtomicUsize::new(0));
+    let thread_done = done.clone();
+    spawn_kernel_thread(move || {
+        suspend_current_and_run_next();
+        preempt_disable();
+        add_timer(get_time_us() + TICK_US, current_task().unwrap());
+        block_current_and_run_next();
+        preempt_enable();
+        thread_done.store(1, Ordering::SeqCst);
+    });
+    wait_until(|| done.load(Ordering::SeqCst) == 1);
+
+    let read_f1 = || {
+        with_fpu(|| {
+            let bits: u64;
+            unsafe { asm!("fmv.x.d {}, f1", out(reg) bits) };
+            bits
+        })
+    };
+    let write_f1 = |bits: u64| with_fpu(|| unsafe { asm!("fmv.d.x f1, {}", in(reg) bits) });
+
+    let irq = disable_interrupts();
+    let mut cx = TrapContext::app_init_context(0, 0, 0, 0, 0);
+    assert!(cx.sstatus.fs() == FS::Off);
+    assert!(enable_fp(&mut cx));
+    assert!(!enable_fp(&mut cx));
+
+    // Loaded once, then left in the registers while this hart still holds it.
+    cx.fp.f[1] = 1;
+    restore_fp_state(&mut cx);
+    assert_eq!(read_f1(), 1);
+    cx.fp.f[1] = 2;
+    restore_fp_state(&mut cx);
+    assert_eq!(read_f1(), 1);
+    forget_fp_owner(&mut cx);
+    restore_fp_state(&mut cx);
+    assert_eq!(read_f1(), 2);
+
+    // Only saved once the task dirtied the registers.
+    write_f1(3);
+    save_fp_state(&mut cx);
+    assert_eq!(cx.fp.f[1], 2);
+    cx.sstatus.set_fs(FS::Dirty);
+    save_fp_state(&mut cx);
+    assert_eq!(cx.fp.f[1], 3);
+    assert!(cx.sstatus.fs() == FS::Clean);
+
+    FP_OWNERS[hart_id()].store(0, Ordering::Relaxed);
+    restore_interrupts(irq);
+    info!("fp_test passed !");
+}
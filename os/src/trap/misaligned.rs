@@ -0,0 +1,204 @@
+use alloc::vec::Vec;
+
+use super::TrapContext;
+use crate::mm::{MapPermission, MemorySet, VirtAddr};
+use crate::task::current_task;
+
+/// Why a misaligned access could not be emulated.
+pub enum EmulationError {
+    /// Not a plain integer load or store.
+    Unsupported,
+    /// The instruction or the memory it accesses is not accessible to the task.
+    Fault,
+}
+
+/// Integer load or store decoded from the faulting instruction.
+struct Access {
+    store: bool,
+    /// Bytes accessed.
+    width: usize,
+    /// Whether a load sign-extends the value.
+    signed: bool,
+    /// `rd` of a load, `rs2` of a store.
+    reg: usize,
+    base: usize,
+    offset: isize,
+    /// Length of the instruction, 2 if compressed.
+    len: usize,
+}
+
+fn bits(inst: u32, hi: u32, lo: u32) -> u32 {
+    (inst >> lo) & ((1 << (hi - lo + 1)) - 1)
+}
+
+fn decode(inst: u32) -> Option<Access> {
+    if inst & 0b11 == 0b11 {
+        decode_32(inst)
+    } else {
+        decode_compressed(inst & 0xffff)
+    }
+}
+
+fn decode_32(inst: u32) -> Option<Access> {
+    let funct3 = bits(inst, 14, 12);
+    let base = bits(inst, 19, 15) as usize;
+    match bits(inst, 6, 0) {
+        // LB, LH, LW, LD, LBU, LHU, LWU.
+        0x03 if funct3 != 7 => Some(Access {
+            store: false,
+            width: 1 << (funct3 & 0b11),
+            signed: funct3 < 4,
+            reg: bits(inst, 11, 7) as usize,
+            base,
+            offset: (inst as i32 >> 20) as isize,
+            len: 4,
+        }),
+        // SB, SH, SW, SD.
+        0x23 if funct3 < 4 => Some(Access {
+            store: true,
+            width: 1 << funct3,
+            signed: false,
+            reg: bits(inst, 24, 20) as usize,
+            base,
+            offset: ((inst as i32 >> 25) << 5 | bits(inst, 11, 7) as i32) as isize,
+            len: 4,
+        }),
+        _ => None,
+    }
+}
+
+fn decode_compressed(inst: u32) -> Option<Access> {
+    // Registers x8 to x15 of the three-bit fields.
+    let reg_low = |lo| bits(inst, lo + 2, lo) as usize + 8;
+    let access = |store, width, reg, base, offset| {
+        Some(Access {
+            store,
+            width,
+            signed: true,
+            reg,
+            base,
+            offset: offset as isize,
+            len: 2,
+        })
+    };
+    let funct3 = bits(inst, 15, 13);
+    match (bits(inst, 1, 0), funct3) {
+        // C.LW, C.LD, C.SW, C.SD.
+        (0b00, 0b010 | 0b110) => {
+            let offset = bits(inst, 12, 10) << 3 | bits(inst, 6, 6) << 2 | bits(inst, 5, 5) << 6;
+            access(funct3 == 0b110, 4, reg_low(2), reg_low(7), offset)
+        }
+        (0b00, 0b011 | 0b111) => {
+            let offset = bits(inst, 12, 10) << 3 | bits(inst, 6, 5) << 6;
+            access(funct3 == 0b111, 8, reg_low(2), reg_low(7), offset)
+        }
+        // C.LWSP, C.LDSP.
+        (0b10, 0b010) => {
+            let offset = bits(inst, 12, 12) << 5 | bits(inst, 6, 4) << 2 | bits(inst, 3, 2) << 6;
+            access(false, 4, bits(inst, 11, 7) as usize, 2, offset)
+        }
+        (0b10, 0b011) => {
+            let offset = bits(inst, 12, 12) << 5 | bits(inst, 6, 5) << 3 | bits(inst, 4, 2) << 6;
+            access(false, 8, bits(inst, 11, 7) as usize, 2, offset)
+        }
+        // C.SWSP, C.SDSP.
+        (0b10, 0b110) => {
+            let offset = bits(inst, 12, 9) << 2 | bits(inst, 8, 7) << 6;
+            access(true, 4, bits(inst, 6, 2) as usize, 2, offset)
+        }
+        (0b10, 0b111) => {
+            let offset = bits(inst, 12, 10) << 3 | bits(inst, 9, 7) << 6;
+            access(true, 8, bits(inst, 6, 2) as usize, 2, offset)
+        }
+        _ => None,
+    }
+}
+
+/// Fetches the instruction at `pc`, only its first half if that says it is compressed.
+fn fetch(memory_set: &MemorySet, pc: usize) -> Option<u32> {
+    let half = |va: usize| -> Option<u32> {
+        let byte = |va| memory_set.user_byte(VirtAddr::from(va), MapPermission::X);
+        Some(*byte(va)? as u32 | (*byte(va + 1)? as u32) << 8)
+    };
+    let low = half(pc)?;
+    if low & 0b11 != 0b11 {
+        return Some(low);
+    }
+    Some(low | half(pc + 2)? << 16)
+}
+
+/// Carries out the misaligned load or store the running task trapped on a byte at a time.
+pub fn emulate_misaligned_access(cx: &mut TrapContext) -> Result<(), EmulationError> {
+    let task = current_task().unwrap();
+    let mm = task.inner_exclusive_access().mm.clone();
+    drop(task);
+    let mm = mm.lock();
+    let memory_set = &mm.memory_set;
+
+    let inst = fetch(memory_set, cx.sepc).ok_or(EmulationError::Fault)?;
+    let access = decode(inst).ok_or(EmulationError::Unsupported)?;
+    let addr = cx.x[access.base].wrapping_add_signed(access.offset);
+
+    if access.store {
+        let value = cx.x[access.reg].to_le_bytes();
+        // Nothing is written unless all of it can be.
+        let bytes = (0..access.width)
+            .map(|i| memory_set.user_byte(VirtAddr::from(addr + i), MapPermission::W))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(EmulationError::Fault)?;
+        for (byte, value) in bytes.into_iter().zip(value) {
+            *byte = value;
+        }
+    } else {
+        let mut value = [0u8; 8];
+        for (i, byte) in value.iter_mut().take(access.width).enumerate() {
+            *byte = *memory_set
+                .user_byte(VirtAddr::from(addr + i), MapPermission::R)
+                .ok_or(EmulationError::Fault)?;
+        }
+        let shift = 64 - 8 * access.width as u32;
+        let value = u64::from_le_bytes(value) << shift;
+        let value = if access.signed {
+            ((value as i64) >> shift) as u64
+        } else {
+            value >> shift
+        };
+        if access.reg != 0 {
+            cx.x[access.reg] = value as usize;
+        }
+    }
+    cx.sepc += access.len;
+    Ok(())
+}
+
+#[cfg(feature = "selftest")]
+pub fn misaligned_test() {
+    use log::info;
+
+    let decoded = |inst| {
+        decode(inst).map(|access| {
+            (
+                access.store,
+                access.width,
+                access.signed,
+                access.reg,
+                access.base,
+                access.offset,
+                access.len,
+            )
+        })
+    };
+    // ld a0, -8(sp); lhu a1, 3(a0); sw a2, -3(s0)
+    assert!(decoded(0xff813503) == Some((false, 8, true, 10, 2, -8, 4)));
+    assert!(decoded(0x00355583) == Some((false, 2, false, 11, 10, 3, 4)));
+    assert!(decoded(0xfec42ea3) == Some((true, 4, false, 12, 8, -3, 4)));
+    // c.ld a0, 8(a1); c.sdsp ra, 16(sp); c.lwsp a0, 4(sp)
+    assert!(decoded(0x6588) == Some((false, 8, true, 10, 11, 8, 2)));
+    assert!(decoded(0xe806) == Some((true, 8, true, 1, 2, 16, 2)));
+    assert!(decoded(0x4512) == Some((false, 4, true, 10, 2, 4, 2)));
+    // amoadd.w and c.fld are left alone.
+    assert!(decoded(0x0005b52f).is_none());
+    assert!(decoded(0x2000).is_none());
+
+    info!("misaligned_test passed !");
+}
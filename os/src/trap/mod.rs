@@ -1,28 +1,87 @@
 mod context;
+mod crash;
+mod fp;
+mod misaligned;
 
 use core::arch::{asm, global_asm};
 
 pub use context::TrapContext;
+#[cfg(feature = "selftest")]
+pub use crash::crash_test;
+use crash::report_crash;
+#[cfg(feature = "selftest")]
+pub use fp::fp_test;
+use fp::{enable_fp, restore_fp_state};
+pub use fp::{forget_fp_owner, save_fp_state};
 use log::error;
-use riscv::register::{mtvec, scause, sie, stval, stvec};
+#[cfg(feature = "selftest")]
+pub use misaligned::misaligned_test;
+use misaligned::{emulate_misaligned_access, EmulationError};
+use riscv::register::{mtvec, scause, sepc, sie, stval, stvec};
 
-use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::config::TRAMPOLINE;
+use crate::smp::{clear_ipi, hart_id};
+use crate::sync::{disable_interrupts, enable_interrupts, preempt_count, set_need_resched};
 use crate::syscall::syscall;
 use crate::task::{
-    current_trap_cx, current_user_token, exit_current_and_run_next, suspend_current_and_run_next,
+    arm_timer, current_exceeds_cpu_limit, current_task, current_trap_cx, current_trap_cx_user_va,
+    current_user_token, dump_core, exit_current_and_run_next, handle_signals,
+    preempt_current_and_run_next, ptrace_syscall_stop, send_signal, timer_tick, touch_watchdog,
+    watchdog_tick, SIGBUS, SIGILL, SIGSEGV, SIGTRAP, SIGXCPU,
 };
-use crate::timer::set_next_trigger;
+use crate::timer::{check_timer, get_time_us};
+use crate::tty::poll_console;
 
 global_asm!(include_str!("trap.asm"));
+global_asm!(include_str!("kernel_trap.asm"));
 
+/// Handles a trap taken in a syscall or a kernel thread. Any exception is a kernel bug.
 #[no_mangle]
-pub fn trap_from_kernel() -> ! {
-    panic!("a trap form kernel!");
+pub fn kernel_trap_handler() {
+    let scause = scause::read();
+    let stval = stval::read();
+    match scause.cause() {
+        scause::Trap::Interrupt(scause::Interrupt::SupervisorTimer) => {
+            check_timer();
+            poll_console();
+            // A kernel path that keeps preemption disabled for long counts as stuck.
+            if preempt_count() == 0 {
+                touch_watchdog();
+            }
+            watchdog_tick();
+            let preempt = timer_tick();
+            arm_timer();
+            if preempt {
+                // Only at a safe point, otherwise once the task enables preemption again.
+                if preempt_count() == 0 {
+                    preempt_current_and_run_next();
+                } else {
+                    set_need_resched();
+                }
+            }
+        }
+        scause::Trap::Interrupt(scause::Interrupt::SupervisorSoft) => {
+            clear_ipi();
+            arm_timer();
+        }
+        _ => {
+            panic!(
+                "Unsupported trap {:?} from kernel, stval = {:#x}, sepc = {:#x}!",
+                scause.cause(),
+                stval,
+                sepc::read()
+            );
+        }
+    }
 }
 
 pub fn set_kernel_trap_entry() {
+    extern "C" {
+        fn __kerneltrap();
+    }
+
     unsafe {
-        stvec::write(trap_from_kernel as usize, mtvec::TrapMode::Direct);
+        stvec::write(__kerneltrap as usize, mtvec::TrapMode::Direct);
     }
 }
 
@@ -36,9 +95,49 @@ pub fn init() {
     set_kernel_trap_entry();
 }
 
+/// Kills the running task for an exception, dumping its core as if it had taken `sig`.
+fn kill_current(what: &str, stval: usize, sig: usize, exit_code: i32) {
+    report_crash(scause::read(), stval);
+    error!("[kernel] {} in application, kernel killed it.", what);
+    dump_core(sig);
+    exit_current_and_run_next(exit_code);
+}
+
+/// Exception code of a misaligned load, which the `riscv` crate does not decode.
+const LOAD_MISALIGNED: usize = 4;
+
+fn emulate_or_kill(cx: &mut TrapContext, stval: usize) {
+    match emulate_misaligned_access(cx) {
+        Ok(()) => {}
+        Err(EmulationError::Fault) => {
+            // The emulated access would have faulted on a page as well.
+            count_page_fault();
+            kill_on_page_fault(stval);
+        }
+        Err(EmulationError::Unsupported) => kill_current("MisalignedAccess", stval, SIGBUS, -2),
+    }
+}
+
+fn count_page_fault() {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .usage
+        .page_faults += 1;
+}
+
+fn kill_on_page_fault(stval: usize) {
+    kill_current("PageFault", stval, SIGSEGV, -2);
+}
+
 #[no_mangle]
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .usage
+        .charge_user(get_time_us());
     let scause = scause::read();
     let stval = stval::read();
     let mut cx = current_trap_cx();
@@ -46,24 +145,69 @@ pub fn trap_handler() -> ! {
     match scause.cause() {
         scause::Trap::Exception(scause::Exception::UserEnvCall) => {
             cx.sepc += 4;
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            // The user state is saved, a long syscall may be preempted.
+            enable_interrupts();
+            ptrace_syscall_stop();
+            // A tracer may have changed the syscall and its arguments.
+            cx = current_trap_cx();
+            let args = [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]];
+            let result = syscall(cx.x[17], args) as usize;
             cx = current_trap_cx();
             cx.x[10] = result;
+            ptrace_syscall_stop();
+        }
+        scause::Trap::Exception(scause::Exception::Breakpoint) => {
+            // Stops a traced task for its tracer, with `sepc` still at the `ebreak`.
+            send_signal(&current_task().unwrap(), SIGTRAP);
+        }
+        scause::Trap::Exception(scause::Exception::StorePageFault)
+        | scause::Trap::Exception(scause::Exception::LoadPageFault)
+        | scause::Trap::Exception(scause::Exception::InstructionPageFault) => {
+            count_page_fault();
+            kill_on_page_fault(stval);
         }
         scause::Trap::Exception(scause::Exception::StoreFault)
-        | scause::Trap::Exception(scause::Exception::StorePageFault)
         | scause::Trap::Exception(scause::Exception::LoadFault)
-        | scause::Trap::Exception(scause::Exception::LoadPageFault) => {
-            error!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.", stval, cx.sepc);
-            exit_current_and_run_next(-2);
+        | scause::Trap::Exception(scause::Exception::InstructionFault) => {
+            kill_on_page_fault(stval);
+        }
+        scause::Trap::Exception(scause::Exception::StoreMisaligned) => {
+            emulate_or_kill(cx, stval);
+        }
+        scause::Trap::Exception(scause::Exception::Unknown) if scause.code() == LOAD_MISALIGNED => {
+            emulate_or_kill(cx, stval);
+        }
+        scause::Trap::Exception(scause::Exception::InstructionMisaligned) => {
+            kill_current("InstructionMisaligned", stval, SIGBUS, -2);
         }
         scause::Trap::Exception(scause::Exception::IllegalInstruction) => {
-            error!("[kernel] IllegalInstruction in application, kernel killed it.");
-            exit_current_and_run_next(-3);
+            // The first floating-point instruction of a task traps with its FPU off.
+            if !enable_fp(cx) {
+                kill_current("IllegalInstruction", stval, SIGILL, -3);
+            }
+        }
+        scause::Trap::Exception(_) => {
+            kill_current("Exception", stval, SIGILL, -3);
         }
         scause::Trap::Interrupt(scause::Interrupt::SupervisorTimer) => {
-            set_next_trigger();
-            suspend_current_and_run_next();
+            check_timer();
+            poll_console();
+            touch_watchdog();
+            watchdog_tick();
+            if current_exceeds_cpu_limit() {
+                error!("[kernel] CPU time limit exceeded in application, sending SIGXCPU.");
+                send_signal(&current_task().unwrap(), SIGXCPU);
+            }
+            let preempt = timer_tick();
+            arm_timer();
+            if preempt {
+                preempt_current_and_run_next();
+            }
+        }
+        scause::Trap::Interrupt(scause::Interrupt::SupervisorSoft) => {
+            // Another hart queued a task while this one was tickless, tick again to preempt.
+            clear_ipi();
+            arm_timer();
         }
         _ => {
             panic!(
@@ -73,13 +217,25 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+    handle_signals();
     trap_return()
 }
 
 #[no_mangle]
 pub fn trap_return() -> ! {
+    // No more traps into the kernel once `stvec` points to the trampoline.
+    disable_interrupts();
     set_user_trap_entry();
-    let trap_cx_ptr = TRAP_CONTEXT;
+    // The task may trap on another hart than it did last time.
+    let cx = current_trap_cx();
+    cx.kernel_tp = hart_id();
+    restore_fp_state(cx);
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .usage
+        .charge_kernel(get_time_us());
+    let trap_cx_ptr = current_trap_cx_user_va();
     let user_satp = current_user_token();
     extern "C" {
         fn __alltraps();
@@ -105,3 +261,10 @@ pub fn enable_timer_interrupt() {
         sie::set_stimer();
     }
 }
+
+/// Lets IPIs from other harts raise a supervisor software interrupt.
+pub fn enable_ipi() {
+    unsafe {
+        sie::set_ssoft();
+    }
+}
@@ -16,10 +16,9 @@ __alltraps:
     sd x1, 1*8(sp)
     # skip sp(x2), we will save it later
     sd x3, 3*8(sp)
-    # skip tp(x4), application does not use it
-    # save x5~x31
-    .set n, 5
-    .rept 27
+    # save tp(x4)~x31
+    .set n, 4
+    .rept 28
         SAVE_GP %n
         .set n, n+1
     .endr
@@ -35,6 +34,8 @@ __alltraps:
     ld t0, 34*8(sp)
     # load trap_handler into t1
     ld t1, 36*8(sp)
+    # load the hart id into tp
+    ld tp, 37*8(sp)
     # move to kernel_sp
     ld sp, 35*8(sp)
     # switch to kernel space
@@ -56,11 +57,11 @@ __restore:
     ld t1, 33*8(sp)
     csrw sstatus, t0
     csrw sepc, t1
-    # restore general purpose registers except x0/sp/tp
+    # restore general purpose registers except x0/sp
     ld x1, 1*8(sp)
     ld x3, 3*8(sp)
-    .set n, 5
-    .rept 27
+    .set n, 4
+    .rept 28
         LOAD_GP %n
         .set n, n+1
     .endr
@@ -0,0 +1,62 @@
+use alloc::collections::VecDeque;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::sbi::console_getchar;
+use crate::sync::SpinLock;
+use crate::task::{kill_pgrp, schedule_work, SIGINT, SIGQUIT, SIGTSTP};
+
+const CTRL_C: u8 = 0x03;
+const CTRL_BACKSLASH: u8 = 0x1c;
+const CTRL_Z: u8 = 0x1a;
+
+/// Characters typed but not read yet, older ones are dropped beyond this.
+const INPUT_CAPACITY: usize = 256;
+
+static INPUT: SpinLock<VecDeque<u8>> = SpinLock::new(VecDeque::new());
+
+/// Process group that job control characters on the console go to.
+static FOREGROUND_PGRP: AtomicUsize = AtomicUsize::new(0);
+
+pub fn foreground_pgrp() -> usize {
+    FOREGROUND_PGRP.load(Ordering::Relaxed)
+}
+
+pub fn set_foreground_pgrp(pgid: usize) {
+    FOREGROUND_PGRP.store(pgid, Ordering::Relaxed);
+}
+
+/// Moves the characters waiting at the console into the input buffer, runs on every timer tick.
+/// Ctrl-C, Ctrl-\ and Ctrl-Z send `SIGINT`, `SIGQUIT` and `SIGTSTP` to the foreground process group
+/// instead, from the workqueue.
+pub fn poll_console() {
+    let mut input = INPUT.lock();
+    loop {
+        let c = console_getchar();
+        // Without input the legacy SBI call returns -1, some implementations 0.
+        if c == 0 || c == usize::MAX {
+            break;
+        }
+        let sig = match c as u8 {
+            CTRL_C => SIGINT,
+            CTRL_BACKSLASH => SIGQUIT,
+            CTRL_Z => SIGTSTP,
+            c => {
+                if input.len() == INPUT_CAPACITY {
+                    input.pop_front();
+                }
+                input.push_back(c);
+                continue;
+            }
+        };
+        let pgid = foreground_pgrp();
+        schedule_work(move || {
+            kill_pgrp(pgid, sig);
+        });
+    }
+}
+
+/// Next typed character, if there is one.
+pub fn tty_getchar() -> Option<u8> {
+    poll_console();
+    INPUT.lock().pop_front()
+}